@@ -0,0 +1,305 @@
+//! Interpreter benchmarks on the contracts of `tests/contracts`, run through
+//! `Runner` on `MemoryBackend`.
+//!
+//! Throughput is in opcodes, counted by a traced run of each call, so that
+//! criterion reports opcodes per second. The storage operations of each call
+//! are printed along with its opcode count, and `SSTORE` and `SLOAD` are
+//! benchmarked on their own.
+//!
+//! `Loop.sol` never returns, as gas is not metered, so its runtime code is
+//! stepped a fixed number of times on a bare `Machine` instead.
+//!
+//! `StandardPrecompiles.sol` is left out, as the standard precompiles are not
+//! implemented; the exit to NEAR precompile is benchmarked instead.
+
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use borsh_crate::BorshSerialize;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use ethabi_contract::use_contract;
+use primitive_types::{H160, H256, U256};
+
+use near_evm::backend::{MemoryAccount, MemoryBackend, MemoryVicinity};
+use near_evm::disasm::split_creation_code;
+use near_evm::evm_core::{Capture, Machine};
+use near_evm::precompiles::EXIT_TO_NEAR_ADDRESS;
+use near_evm::runner::{Config, CreateScheme, Runner};
+use near_evm::tracer::{Step, Tracer};
+use near_evm::types::{keccak, near_account_to_evm_address, FunctionCallArgs, TransactionStatus};
+
+use_contract!(fibonacci, "tests/build/Fibonacci.abi");
+use_contract!(ttoken, "tests/build/TToken.abi");
+use_contract!(bpool, "tests/build/BPool.abi");
+
+/// Steps of `Loop.sol`.
+const LOOP_STEPS: u64 = 100_000;
+/// Slots written or read by the storage benchmarks.
+const STORAGE_SLOTS: u8 = 100;
+
+fn alice_addr() -> H160 {
+    near_account_to_evm_address(b"alice")
+}
+
+fn bob_addr() -> H160 {
+    near_account_to_evm_address(b"bob")
+}
+
+fn vicinity() -> MemoryVicinity {
+    MemoryVicinity {
+        origin: alice_addr(),
+        chain_id: U256::one(),
+        ..Default::default()
+    }
+}
+
+fn deploy(backend: &mut MemoryBackend, input: Vec<u8>) -> H160 {
+    let receipt = Runner::deploy_code(backend, &input);
+    assert_eq!(receipt.status, TransactionStatus::Succeed);
+    H160(receipt.contract_address.unwrap())
+}
+
+fn call_args(address: H160, input: Vec<u8>) -> Vec<u8> {
+    FunctionCallArgs {
+        contract: address.0,
+        input,
+    }
+    .try_to_vec()
+    .unwrap()
+}
+
+fn call(backend: &mut MemoryBackend, address: H160, input: Vec<u8>) -> Vec<u8> {
+    let receipt = Runner::call(backend, &call_args(address, input));
+    assert_eq!(receipt.status, TransactionStatus::Succeed);
+    receipt.result
+}
+
+fn create_ttoken(backend: &mut MemoryBackend) -> H160 {
+    let input = ttoken::constructor(
+        hex::decode(&include_bytes!("../tests/build/TToken.bin").to_vec()).unwrap(),
+        "XYZ",
+        "XYZ",
+        18,
+    );
+    let address = deploy(backend, input);
+    let (input, _) = ttoken::functions::mint::call(&alice_addr().0, 100 * 10u128.pow(18));
+    call(backend, address, input);
+    address
+}
+
+/// Opcodes and storage operations of a call.
+#[derive(Default)]
+struct Counter {
+    opcodes: u64,
+    sloads: u64,
+    sstores: u64,
+}
+
+impl Tracer for Counter {
+    fn before_step(&mut self, _step: &Step) {
+        self.opcodes += 1;
+    }
+
+    fn sload(&mut self, _address: H160, _index: H256, _value: H256) {
+        self.sloads += 1;
+    }
+
+    fn sstore(&mut self, _address: H160, _index: H256, _value: H256) {
+        self.sstores += 1;
+    }
+}
+
+/// Benchmarks a call from alice, each iteration running on a copy of
+/// `backend` and committing to it.
+fn bench_call(
+    c: &mut Criterion,
+    name: &str,
+    backend: &MemoryBackend,
+    address: H160,
+    input: Vec<u8>,
+) {
+    let args = call_args(address, input);
+    let mut counter = Counter::default();
+    let receipt = Runner::call_with_tracer(&mut backend.clone(), &args, &mut counter);
+    assert_eq!(receipt.status, TransactionStatus::Succeed);
+    println!(
+        "{}: {} opcodes, {} SLOAD, {} SSTORE",
+        name, counter.opcodes, counter.sloads, counter.sstores
+    );
+
+    let mut group = c.benchmark_group(name);
+    group.throughput(Throughput::Elements(counter.opcodes));
+    group.bench_function("call", |b| {
+        b.iter_batched(
+            || backend.clone(),
+            |mut backend| Runner::call(&mut backend, &args),
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+fn bench_fibonacci(c: &mut Criterion) {
+    let vicinity = vicinity();
+    let mut backend = MemoryBackend::new(&vicinity, BTreeMap::new());
+    let address = deploy(
+        &mut backend,
+        hex::decode(&include_bytes!("../tests/build/Fibonacci.bin").to_vec()).unwrap(),
+    );
+    let (input, _) = fibonacci::functions::fibonacci::call(20);
+    bench_call(c, "fibonacci(20)", &backend, address, input);
+}
+
+fn bench_loop(c: &mut Criterion) {
+    let code = hex::decode(&include_bytes!("../tests/build/Loop.bin").to_vec()).unwrap();
+    let runtime = Rc::new(split_creation_code(&code).unwrap().runtime.to_vec());
+    let input = Rc::new(keccak(b"run()")[..4].to_vec());
+
+    let mut group = c.benchmark_group("loop");
+    group.throughput(Throughput::Elements(LOOP_STEPS));
+    group.bench_function("machine", |b| {
+        b.iter(|| {
+            let mut machine =
+                Machine::new(runtime.clone(), input.clone(), 1024, usize::max_value());
+            for _ in 0..LOOP_STEPS {
+                if let Err(Capture::Exit(reason)) = machine.step() {
+                    panic!("{:?}", reason);
+                }
+            }
+        })
+    });
+    group.finish();
+}
+
+/// Benchmarks a call from alice to an account with `code` and `storage`,
+/// each iteration running on a new copy of the state.
+fn bench_code(c: &mut Criterion, name: &str, code: Vec<u8>, storage: BTreeMap<H256, H256>) {
+    let address = H160::from_low_u64_be(0xc0de);
+    let mut state = BTreeMap::new();
+    state.insert(
+        address,
+        MemoryAccount {
+            code,
+            storage,
+            ..Default::default()
+        },
+    );
+    let vicinity = vicinity();
+    let backend = MemoryBackend::new(&vicinity, state);
+
+    let mut group = c.benchmark_group("storage");
+    group.throughput(Throughput::Elements(STORAGE_SLOTS as u64));
+    group.bench_function(name, |b| {
+        b.iter_batched(
+            || backend.clone(),
+            |mut backend| {
+                let execution =
+                    Runner::execute(&mut backend, U256::zero(), true, false, None, |executor| {
+                        executor.transact_call(alice_addr(), address, U256::zero(), Vec::new())
+                    });
+                assert!(execution.reason.is_succeed());
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+fn bench_storage(c: &mut Criterion) {
+    // SSTORE(slot, slot + 1) for every slot, to empty slots.
+    let mut code = Vec::new();
+    for slot in 0..STORAGE_SLOTS {
+        code.extend_from_slice(&[0x60, slot + 1, 0x60, slot, 0x55]);
+    }
+    bench_code(c, "sstore", code, BTreeMap::new());
+
+    // POP(SLOAD(slot)) for every slot, from written slots.
+    let mut code = Vec::new();
+    for slot in 0..STORAGE_SLOTS {
+        code.extend_from_slice(&[0x60, slot, 0x54, 0x50]);
+    }
+    let storage = (0..STORAGE_SLOTS as u64)
+        .map(|slot| (H256::from_low_u64_be(slot), H256::from_low_u64_be(slot + 1)))
+        .collect();
+    bench_code(c, "sload", code, storage);
+}
+
+fn bench_ttoken(c: &mut Criterion) {
+    let vicinity = vicinity();
+    let mut backend = MemoryBackend::new(&vicinity, BTreeMap::new());
+    let address = create_ttoken(&mut backend);
+    let (input, _) = ttoken::functions::transfer::call(&bob_addr().0, 10u128.pow(18));
+    bench_call(c, "ttoken transfer", &backend, address, input);
+}
+
+fn bench_bpool(c: &mut Criterion) {
+    let vicinity = vicinity();
+    let mut backend = MemoryBackend::new(&vicinity, BTreeMap::new());
+    // BPool, and BFactory which embeds it, exceed the EIP-170 contract size
+    // limit, so the pool is deployed directly with the limit lifted.
+    let mut config = Config::istanbul();
+    config.create_contract_limit = None;
+    let code = hex::decode(&include_bytes!("../tests/build/BPool.bin").to_vec()).unwrap();
+    let execution =
+        Runner::execute_with_config(&mut backend, &config, true, false, None, |executor| {
+            let address = executor.create_address(CreateScheme::Legacy {
+                caller: alice_addr(),
+            });
+            let (reason, _) = executor.transact_create(alice_addr(), U256::zero(), code);
+            (reason, address)
+        });
+    assert!(execution.reason.is_succeed());
+    let pool = execution.result;
+    let tokens = [create_ttoken(&mut backend), create_ttoken(&mut backend)];
+    for token in tokens.iter() {
+        let (input, _) = ttoken::functions::approve::call(&pool.0, 100 * 10u128.pow(18));
+        call(&mut backend, *token, input);
+        let (input, _) =
+            bpool::functions::bind::call(*token, 10 * 10u128.pow(18), 5 * 10u128.pow(18));
+        call(&mut backend, pool, input);
+    }
+    let (input, _) = bpool::functions::finalize::call();
+    call(&mut backend, pool, input);
+
+    let (input, _) = bpool::functions::swap_exact_amount_in::call(
+        tokens[0],
+        10u128.pow(16),
+        tokens[1],
+        0,
+        u128::max_value(),
+    );
+    bench_call(c, "bpool swapExactAmountIn", &backend, pool, input);
+}
+
+fn bench_precompiles(c: &mut Criterion) {
+    let vicinity = vicinity();
+    let mut backend = MemoryBackend::new(&vicinity, BTreeMap::new());
+    let mut group = c.benchmark_group("precompiles");
+    group.bench_function("exit_to_near", |b| {
+        b.iter(|| {
+            let execution =
+                Runner::execute(&mut backend, U256::zero(), false, false, None, |executor| {
+                    executor.deposit(alice_addr(), U256::one());
+                    executor.transact_call(
+                        alice_addr(),
+                        EXIT_TO_NEAR_ADDRESS,
+                        U256::one(),
+                        b"bob.near".to_vec(),
+                    )
+                });
+            assert!(execution.reason.is_succeed());
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_fibonacci,
+    bench_loop,
+    bench_storage,
+    bench_ttoken,
+    bench_bpool,
+    bench_precompiles
+);
+criterion_main!(benches);
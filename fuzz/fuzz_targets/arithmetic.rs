@@ -0,0 +1,67 @@
+//! Arithmetic, comparison and bitwise opcodes on operands biased towards the
+//! edge cases of signed arithmetic.
+#![no_main]
+
+use libfuzzer_sys::arbitrary::{self, Arbitrary};
+use libfuzzer_sys::fuzz_target;
+
+/// Opcodes taking only stack operands, from `ADD` to `SAR`.
+const OPCODES: &[u8] = &[
+    0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x10, 0x11, 0x12, 0x13,
+    0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d,
+];
+
+/// Opcodes run by an input, with enough operands pushed for each.
+const MAX_OPS: usize = 64;
+
+#[derive(Arbitrary, Debug)]
+enum Word {
+    Zero,
+    One,
+    /// All bits set, -1 as a signed integer.
+    Max,
+    /// Lowest signed integer, -2^255.
+    Min,
+    /// Highest signed integer, 2^255 - 1.
+    SignedMax,
+    Small(u8),
+    Random([u8; 32]),
+}
+
+impl Word {
+    fn to_bytes(&self) -> [u8; 32] {
+        let mut result = [0u8; 32];
+        match self {
+            Word::Zero => (),
+            Word::One => result[31] = 1,
+            Word::Max => result = [0xff; 32],
+            Word::Min => result[0] = 0x80,
+            Word::SignedMax => {
+                result = [0xff; 32];
+                result[0] = 0x7f;
+            }
+            Word::Small(value) => result[31] = *value,
+            Word::Random(value) => result = *value,
+        }
+        result
+    }
+}
+
+#[derive(Arbitrary, Debug)]
+struct Op {
+    opcode: u8,
+    operands: [Word; 3],
+}
+
+fuzz_target!(|ops: Vec<Op>| {
+    let mut code = Vec::new();
+    for op in ops.iter().take(MAX_OPS) {
+        for operand in op.operands.iter().rev() {
+            // PUSH32
+            code.push(0x7f);
+            code.extend_from_slice(&operand.to_bytes());
+        }
+        code.push(OPCODES[op.opcode as usize % OPCODES.len()]);
+    }
+    near_evm_fuzz::check(&code, &[]);
+});
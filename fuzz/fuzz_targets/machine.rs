@@ -0,0 +1,15 @@
+//! Random bytecode and call data.
+#![no_main]
+
+use libfuzzer_sys::arbitrary::{self, Arbitrary};
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    code: Vec<u8>,
+    data: Vec<u8>,
+}
+
+fuzz_target!(|input: Input| {
+    near_evm_fuzz::check(&input.code, &input.data);
+});
@@ -0,0 +1,214 @@
+//! Differential execution of bytecode on `near_evm::evm_core::Machine`, the
+//! upstream `evm-core` interpreter it was forked from, and revm's interpreter.
+//!
+//! The machines run until they exit, trap on an opcode needing a handler
+//! (e.g. `SLOAD` or `CALL`), or reach `STEP_LIMIT`, as gas is not metered.
+//! revm answers such opcodes from a dummy host and meters gas, so it is only
+//! compared on runs that exit on their own within the limits.
+
+use std::rc::Rc;
+
+use near_evm::evm_core as near;
+use revm_interpreter::primitives::{Bytecode, Bytes, Env, IstanbulSpec, B160, U256};
+use revm_interpreter::{Contract, DummyHost, InstructionResult, Interpreter};
+
+const STACK_LIMIT: usize = 1024;
+/// Lower than in the contract, so that large offsets fail instead of running
+/// out of memory.
+const MEMORY_LIMIT: usize = 1 << 20;
+const STEP_LIMIT: usize = 10_000;
+
+/// How execution stopped, in terms all interpreters share.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Exit {
+    Stop,
+    Return,
+    Revert,
+    StackUnderflow,
+    StackOverflow,
+    InvalidJump,
+    /// `RETURNDATACOPY` past the end of the return data.
+    OutOfOffset,
+    /// The designated invalid opcode `0xfe`.
+    DesignatedInvalid,
+    /// Memory beyond `MEMORY_LIMIT`, or an operand too large to be an offset.
+    /// revm reports these as running out of gas.
+    Limit,
+    /// Exits that need a handler, e.g. `CallTooDeep`.
+    Other,
+    /// Trapped on the opcode at this position.
+    Trap(usize),
+    StepLimit,
+}
+
+/// Maps the exit reason of an `evm-core` machine, which the fork defines like
+/// upstream.
+macro_rules! core_exit {
+    ($core:ident, $reason:expr) => {
+        match $reason {
+            $core::ExitReason::Succeed($core::ExitSucceed::Returned) => Exit::Return,
+            $core::ExitReason::Succeed(_) => Exit::Stop,
+            $core::ExitReason::Revert(_) => Exit::Revert,
+            $core::ExitReason::Error($core::ExitError::StackUnderflow) => Exit::StackUnderflow,
+            $core::ExitReason::Error($core::ExitError::StackOverflow) => Exit::StackOverflow,
+            $core::ExitReason::Error($core::ExitError::InvalidJump) => Exit::InvalidJump,
+            $core::ExitReason::Error($core::ExitError::OutOfOffset) => Exit::OutOfOffset,
+            $core::ExitReason::Error($core::ExitError::DesignatedInvalid) => {
+                Exit::DesignatedInvalid
+            }
+            $core::ExitReason::Error($core::ExitError::InvalidRange)
+            | $core::ExitReason::Fatal($core::ExitFatal::NotSupported) => Exit::Limit,
+            _ => Exit::Other,
+        }
+    };
+}
+
+fn revm_exit(result: InstructionResult) -> Exit {
+    match result {
+        InstructionResult::Stop | InstructionResult::SelfDestruct => Exit::Stop,
+        InstructionResult::Return => Exit::Return,
+        InstructionResult::Revert => Exit::Revert,
+        InstructionResult::StackUnderflow => Exit::StackUnderflow,
+        InstructionResult::StackOverflow => Exit::StackOverflow,
+        InstructionResult::InvalidJump => Exit::InvalidJump,
+        InstructionResult::OutOfOffset => Exit::OutOfOffset,
+        InstructionResult::InvalidFEOpcode => Exit::DesignatedInvalid,
+        InstructionResult::OutOfGas
+        | InstructionResult::MemoryOOG
+        | InstructionResult::MemoryLimitOOG
+        | InstructionResult::InvalidOperandOOG => Exit::Limit,
+        _ => Exit::Other,
+    }
+}
+
+/// State of a machine after execution.
+#[derive(Debug, Eq, PartialEq)]
+pub struct Outcome {
+    pub exit: Exit,
+    pub stack: Vec<[u8; 32]>,
+    pub memory: Vec<u8>,
+    pub return_value: Vec<u8>,
+}
+
+pub fn run_near(code: &[u8], data: &[u8]) -> Outcome {
+    let mut machine = near::Machine::new(
+        Rc::new(code.to_vec()),
+        Rc::new(data.to_vec()),
+        STACK_LIMIT,
+        MEMORY_LIMIT,
+    );
+    let mut exit = Exit::StepLimit;
+    for _ in 0..STEP_LIMIT {
+        match machine.step() {
+            Ok(()) => (),
+            Err(near::Capture::Exit(reason)) => {
+                exit = core_exit!(near, reason);
+                break;
+            }
+            Err(near::Capture::Trap(_)) => {
+                exit = Exit::Trap(machine.last_step().0);
+                break;
+            }
+        }
+    }
+    let memory = machine.memory();
+    Outcome {
+        exit,
+        stack: machine.stack().data().iter().map(|value| value.0).collect(),
+        memory: memory.get(0, memory.len()),
+        return_value: machine.return_value(),
+    }
+}
+
+pub fn run_reference(code: &[u8], data: &[u8]) -> Outcome {
+    let mut machine = evm_core::Machine::new(
+        Rc::new(code.to_vec()),
+        Rc::new(data.to_vec()),
+        STACK_LIMIT,
+        MEMORY_LIMIT,
+    );
+    let mut exit = Exit::StepLimit;
+    for _ in 0..STEP_LIMIT {
+        match machine.step() {
+            Ok(()) => (),
+            Err(evm_core::Capture::Exit(reason)) => {
+                exit = core_exit!(evm_core, reason);
+                break;
+            }
+            Err(evm_core::Capture::Trap(_)) => {
+                // The position is past the trapping opcode, to resume after
+                // it is handled.
+                let position = machine.position().as_ref().map_or(0, |position| *position);
+                exit = Exit::Trap(position.saturating_sub(1));
+                break;
+            }
+        }
+    }
+    Outcome {
+        exit,
+        stack: machine.stack().data().iter().map(|value| value.0).collect(),
+        memory: machine.memory().data().clone(),
+        return_value: machine.return_value(),
+    }
+}
+
+/// Runs on revm with all the gas it can use, as the machines are bounded by
+/// `STEP_LIMIT` and `MEMORY_LIMIT` instead.
+pub fn run_revm(code: &[u8], data: &[u8]) -> Outcome {
+    let contract = Contract::new(
+        Bytes::copy_from_slice(data),
+        Bytecode::new_raw(Bytes::copy_from_slice(code)),
+        B160::zero(),
+        B160::zero(),
+        U256::ZERO,
+    );
+    let mut interpreter = Interpreter::new(Box::new(contract), u64::MAX, false);
+    let mut host = DummyHost::new(Env::default());
+    let mut exit = Exit::StepLimit;
+    for _ in 0..STEP_LIMIT {
+        interpreter.step::<DummyHost, IstanbulSpec>(&mut host);
+        if interpreter.instruction_result != InstructionResult::Continue {
+            exit = revm_exit(interpreter.instruction_result);
+            break;
+        }
+    }
+    Outcome {
+        exit,
+        stack: interpreter
+            .stack
+            .data()
+            .iter()
+            .map(|value| value.to_be_bytes::<32>())
+            .collect(),
+        memory: interpreter.memory.data().clone(),
+        return_value: interpreter.return_value().to_vec(),
+    }
+}
+
+/// Panics if `code` called with `data` executes differently on the
+/// interpreters.
+pub fn check(code: &[u8], data: &[u8]) {
+    let outcome = run_near(code, data);
+    let expected = run_reference(code, data);
+    assert_eq!(
+        outcome,
+        expected,
+        "diverged from evm-core on code {} with data {}",
+        hex(code),
+        hex(data)
+    );
+    match outcome.exit {
+        Exit::Trap(_) | Exit::StepLimit | Exit::Limit => (),
+        _ => assert_eq!(
+            outcome,
+            run_revm(code, data),
+            "diverged from revm on code {} with data {}",
+            hex(code),
+            hex(data)
+        ),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    near_evm::types::bytes_to_hex(bytes)
+}
@@ -0,0 +1,197 @@
+use super::{Apply, ApplyBackend, Backend, Basic, Log};
+#[cfg(feature = "std")]
+use std::{collections::BTreeMap, vec::Vec};
+
+#[cfg(not(feature = "std"))]
+use alloc::{collections::BTreeMap, vec::Vec};
+
+use core::cell::{Cell, RefCell};
+
+use primitive_types::{H160, H256, U256};
+
+/// Account values read through a cached backend.
+#[derive(Default)]
+struct CachedAccount {
+    exists: Option<bool>,
+    basic: Option<Basic>,
+    code_hash: Option<H256>,
+    code_size: Option<usize>,
+    code: Option<Vec<u8>>,
+}
+
+/// Backend wrapper memoizing account, code and storage reads of the inner
+/// backend. Meant to live for a single transaction: the cache is cleared
+/// when changes are applied.
+pub struct CachedBackend<B> {
+    backend: B,
+    accounts: RefCell<BTreeMap<H160, CachedAccount>>,
+    storage: RefCell<BTreeMap<(H160, H256), H256>>,
+    hits: Cell<u64>,
+    misses: Cell<u64>,
+}
+
+impl<B: Backend> CachedBackend<B> {
+    /// Create a new cached backend.
+    pub fn new(backend: B) -> Self {
+        Self {
+            backend,
+            accounts: RefCell::new(BTreeMap::new()),
+            storage: RefCell::new(BTreeMap::new()),
+            hits: Cell::new(0),
+            misses: Cell::new(0),
+        }
+    }
+
+    /// Get the inner backend.
+    pub fn inner(&self) -> &B {
+        &self.backend
+    }
+
+    /// Consume the cached backend, returning the inner one.
+    pub fn into_inner(self) -> B {
+        self.backend
+    }
+
+    /// Number of reads served from the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits.get()
+    }
+
+    /// Number of reads passed to the inner backend.
+    pub fn misses(&self) -> u64 {
+        self.misses.get()
+    }
+
+    fn cached<T, F, R>(&self, address: H160, field: F, read: R) -> T
+    where
+        T: Clone,
+        F: FnOnce(&mut CachedAccount) -> &mut Option<T>,
+        R: FnOnce(&B) -> T,
+    {
+        let mut accounts = self.accounts.borrow_mut();
+        let value = field(accounts.entry(address).or_default());
+        match value {
+            Some(value) => {
+                self.hits.set(self.hits.get() + 1);
+                value.clone()
+            }
+            None => {
+                self.misses.set(self.misses.get() + 1);
+                value.get_or_insert(read(&self.backend)).clone()
+            }
+        }
+    }
+}
+
+impl<B: Backend> Backend for CachedBackend<B> {
+    fn gas_left(&self) -> U256 {
+        self.backend.gas_left()
+    }
+    fn gas_price(&self) -> U256 {
+        self.backend.gas_price()
+    }
+    fn origin(&self) -> H160 {
+        self.backend.origin()
+    }
+    fn block_hash(&self, number: U256) -> H256 {
+        self.backend.block_hash(number)
+    }
+    fn block_number(&self) -> U256 {
+        self.backend.block_number()
+    }
+    fn block_coinbase(&self) -> H160 {
+        self.backend.block_coinbase()
+    }
+    fn block_timestamp(&self) -> U256 {
+        self.backend.block_timestamp()
+    }
+    fn block_difficulty(&self) -> U256 {
+        self.backend.block_difficulty()
+    }
+    fn block_gas_limit(&self) -> U256 {
+        self.backend.block_gas_limit()
+    }
+    fn chain_id(&self) -> U256 {
+        self.backend.chain_id()
+    }
+
+    fn exists(&self, address: H160) -> bool {
+        self.cached(address, |a| &mut a.exists, |b| b.exists(address))
+    }
+
+    fn basic(&self, address: H160) -> Basic {
+        self.cached(address, |a| &mut a.basic, |b| b.basic(address))
+    }
+
+    fn code_hash(&self, address: H160) -> H256 {
+        self.cached(address, |a| &mut a.code_hash, |b| b.code_hash(address))
+    }
+
+    fn code_size(&self, address: H160) -> usize {
+        self.cached(address, |a| &mut a.code_size, |b| b.code_size(address))
+    }
+
+    fn code(&self, address: H160) -> Vec<u8> {
+        self.cached(address, |a| &mut a.code, |b| b.code(address))
+    }
+
+    fn storage(&self, address: H160, index: H256) -> H256 {
+        let mut storage = self.storage.borrow_mut();
+        match storage.get(&(address, index)) {
+            Some(value) => {
+                self.hits.set(self.hits.get() + 1);
+                *value
+            }
+            None => {
+                self.misses.set(self.misses.get() + 1);
+                let value = self.backend.storage(address, index);
+                storage.insert((address, index), value);
+                value
+            }
+        }
+    }
+}
+
+impl<B: Backend + ApplyBackend> ApplyBackend for CachedBackend<B> {
+    fn apply<A, I, L>(&mut self, values: A, logs: L, delete_empty: bool)
+    where
+        A: IntoIterator<Item = Apply<I>>,
+        I: IntoIterator<Item = (H256, H256)>,
+        L: IntoIterator<Item = Log>,
+    {
+        self.accounts.borrow_mut().clear();
+        self.storage.borrow_mut().clear();
+        self.backend.apply(values, logs, delete_empty)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{MemoryAccount, MemoryBackend, MemoryVicinity};
+    use super::*;
+
+    #[test]
+    fn test_cached_backend() {
+        let vicinity = MemoryVicinity::default();
+        let address = H160::from_low_u64_be(1);
+        let mut state = BTreeMap::new();
+        state.insert(
+            address,
+            MemoryAccount {
+                nonce: U256::one(),
+                balance: U256::from(10),
+                storage: BTreeMap::new(),
+                code: vec![0x00],
+            },
+        );
+        let backend = CachedBackend::new(MemoryBackend::new(&vicinity, state));
+
+        assert_eq!(backend.basic(address).balance, U256::from(10));
+        assert_eq!(backend.basic(address).nonce, U256::one());
+        assert_eq!(backend.code(address), vec![0x00]);
+        assert_eq!(backend.storage(address, H256::zero()), H256::zero());
+        assert_eq!(backend.storage(address, H256::zero()), H256::zero());
+        assert_eq!(backend.hits(), 2);
+        assert_eq!(backend.misses(), 3);
+    }
+}
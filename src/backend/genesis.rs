@@ -0,0 +1,147 @@
+//! Geth-style genesis `alloc` JSON of memory backend state.
+
+#[cfg(feature = "std")]
+use std::{collections::BTreeMap, format, string::String, vec::Vec};
+
+#[cfg(not(feature = "std"))]
+use alloc::{collections::BTreeMap, format, string::String, vec::Vec};
+
+use primitive_types::{H160, H256, U256};
+use serde_json::{Map, Value};
+
+use super::MemoryAccount;
+use crate::types::{bytes_to_hex, hex_to_bytes};
+
+/// Dumps the state as a genesis `alloc` object. Quantities and data are hex
+/// encoded, zero nonce, empty code and empty storage are left out.
+pub fn state_to_genesis_alloc(state: &BTreeMap<H160, MemoryAccount>) -> Value {
+    let mut alloc = Map::new();
+    for (address, account) in state.iter() {
+        let mut entry = Map::new();
+        entry.insert(
+            String::from("balance"),
+            Value::String(format!("{:#x}", account.balance)),
+        );
+        if !account.nonce.is_zero() {
+            entry.insert(
+                String::from("nonce"),
+                Value::String(format!("{:#x}", account.nonce)),
+            );
+        }
+        if !account.code.is_empty() {
+            entry.insert(String::from("code"), Value::String(to_hex(&account.code)));
+        }
+        if !account.storage.is_empty() {
+            let storage = account
+                .storage
+                .iter()
+                .map(|(key, value)| (to_hex(&key.0), Value::String(to_hex(&value.0))))
+                .collect();
+            entry.insert(String::from("storage"), Value::Object(storage));
+        }
+        alloc.insert(to_hex(&address.0), Value::Object(entry));
+    }
+    Value::Object(alloc)
+}
+
+/// Loads the state from a genesis `alloc` object. Balances and nonces can be
+/// decimal or `0x` prefixed hex, as accepted by geth.
+pub fn state_from_genesis_alloc(
+    alloc: &Value,
+) -> Result<BTreeMap<H160, MemoryAccount>, &'static str> {
+    let alloc = alloc.as_object().ok_or("ERR_INVALID_ALLOC")?;
+    let mut state = BTreeMap::new();
+    for (address, entry) in alloc.iter() {
+        let address = match from_hex(address) {
+            Some(address) if address.len() == 20 => H160::from_slice(&address),
+            _ => return Err("ERR_INVALID_ADDRESS"),
+        };
+        let entry = entry.as_object().ok_or("ERR_INVALID_ACCOUNT")?;
+        let mut account = MemoryAccount::default();
+        if let Some(balance) = entry.get("balance") {
+            account.balance = to_quantity(balance).ok_or("ERR_INVALID_BALANCE")?;
+        }
+        if let Some(nonce) = entry.get("nonce") {
+            account.nonce = to_quantity(nonce).ok_or("ERR_INVALID_NONCE")?;
+        }
+        if let Some(code) = entry.get("code") {
+            account.code = code.as_str().and_then(from_hex).ok_or("ERR_INVALID_CODE")?;
+        }
+        if let Some(storage) = entry.get("storage") {
+            let storage = storage.as_object().ok_or("ERR_INVALID_STORAGE")?;
+            for (key, value) in storage.iter() {
+                let key = to_h256(key).ok_or("ERR_INVALID_STORAGE")?;
+                let value = value
+                    .as_str()
+                    .and_then(to_h256)
+                    .ok_or("ERR_INVALID_STORAGE")?;
+                account.storage.insert(key, value);
+            }
+        }
+        state.insert(address, account);
+    }
+    Ok(state)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    format!("0x{}", bytes_to_hex(bytes))
+}
+
+fn from_hex(value: &str) -> Option<Vec<u8>> {
+    hex_to_bytes(value.trim_start_matches("0x"))
+}
+
+/// Left pads up to 32 bytes of hex to a hash.
+fn to_h256(value: &str) -> Option<H256> {
+    let bytes = from_hex(value)?;
+    if bytes.len() > 32 {
+        return None;
+    }
+    let mut result = H256::zero();
+    result.0[32 - bytes.len()..].copy_from_slice(&bytes);
+    Some(result)
+}
+
+fn to_quantity(value: &Value) -> Option<U256> {
+    match value {
+        Value::String(value) if value.starts_with("0x") => {
+            U256::from_str_radix(&value[2..], 16).ok()
+        }
+        Value::String(value) => U256::from_dec_str(value).ok(),
+        Value::Number(value) => value.as_u64().map(U256::from),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_genesis_alloc() {
+        let alloc: Value = serde_json::from_str(
+            r#"{
+                "0x0000000000000000000000000000000000000001": {"balance": "1000"},
+                "a94f5374fce5edbc8e2a8697c15331677e6ebf0b": {
+                    "balance": "0x0de0b6b3a7640000",
+                    "nonce": "0x1",
+                    "code": "0x6001600055",
+                    "storage": {"0x00": "0x01"}
+                }
+            }"#,
+        )
+        .unwrap();
+        let state = state_from_genesis_alloc(&alloc).unwrap();
+        let account = &state
+            [&H160::from_slice(&hex_to_bytes("a94f5374fce5edbc8e2a8697c15331677e6ebf0b").unwrap())];
+        assert_eq!(account.balance, U256::exp10(18));
+        assert_eq!(account.nonce, U256::one());
+        assert_eq!(account.code, vec![0x60, 0x01, 0x60, 0x00, 0x55]);
+        assert_eq!(account.storage[&H256::zero()], H256::from_low_u64_be(1));
+        assert_eq!(state[&H160::from_low_u64_be(1)].balance, U256::from(1000));
+        assert_eq!(
+            state_from_genesis_alloc(&state_to_genesis_alloc(&state)),
+            Ok(state)
+        );
+    }
+}
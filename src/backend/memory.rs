@@ -5,12 +5,24 @@ use std::{collections::BTreeMap, vec::Vec};
 #[cfg(not(feature = "std"))]
 use alloc::{collections::BTreeMap, vec::Vec};
 
+#[cfg(feature = "borsh")]
+use borsh::maybestd::io::{self, Write};
+#[cfg(feature = "borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
 use primitive_types::{H160, H256, U256};
 
+use crate::trie::{account_rlp, sec_trie_proof, sec_trie_root, storage_proofs, storage_root};
+use crate::types::{keccak, u256_to_arr, AccountProof};
+#[cfg(feature = "borsh")]
+use crate::types::{RawAddress, RawH256, RawU256};
+
 /// Vivinity value of a memory backend.
-#[derive(Clone, Debug, Eq, PartialEq)]
-#[cfg_attr(feature = "with-codec", derive(codec::Encode, codec::Decode))]
-#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Default, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_crate::Serialize, serde_crate::Deserialize),
+    serde(crate = "serde_crate")
+)]
 pub struct MemoryVicinity {
     /// Gas price.
     pub gas_price: U256,
@@ -34,8 +46,11 @@ pub struct MemoryVicinity {
 
 /// Account information of a memory backend.
 #[derive(Default, Clone, Debug, Eq, PartialEq)]
-#[cfg_attr(feature = "with-codec", derive(codec::Encode, codec::Decode))]
-#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_crate::Serialize, serde_crate::Deserialize),
+    serde(crate = "serde_crate")
+)]
 pub struct MemoryAccount {
     /// Account nonce.
     pub nonce: U256,
@@ -47,6 +62,72 @@ pub struct MemoryAccount {
     pub code: Vec<u8>,
 }
 
+#[cfg(feature = "borsh")]
+impl BorshSerialize for MemoryVicinity {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        u256_to_arr(&self.gas_price).serialize(writer)?;
+        self.origin.0.serialize(writer)?;
+        u256_to_arr(&self.chain_id).serialize(writer)?;
+        let block_hashes: Vec<RawH256> = self.block_hashes.iter().map(|hash| hash.0).collect();
+        block_hashes.serialize(writer)?;
+        u256_to_arr(&self.block_number).serialize(writer)?;
+        self.block_coinbase.0.serialize(writer)?;
+        u256_to_arr(&self.block_timestamp).serialize(writer)?;
+        u256_to_arr(&self.block_difficulty).serialize(writer)?;
+        u256_to_arr(&self.block_gas_limit).serialize(writer)
+    }
+}
+
+#[cfg(feature = "borsh")]
+impl BorshDeserialize for MemoryVicinity {
+    fn deserialize(buf: &mut &[u8]) -> io::Result<Self> {
+        Ok(Self {
+            gas_price: U256::from_big_endian(&RawU256::deserialize(buf)?),
+            origin: H160(RawAddress::deserialize(buf)?),
+            chain_id: U256::from_big_endian(&RawU256::deserialize(buf)?),
+            block_hashes: Vec::<RawH256>::deserialize(buf)?
+                .into_iter()
+                .map(H256)
+                .collect(),
+            block_number: U256::from_big_endian(&RawU256::deserialize(buf)?),
+            block_coinbase: H160(RawAddress::deserialize(buf)?),
+            block_timestamp: U256::from_big_endian(&RawU256::deserialize(buf)?),
+            block_difficulty: U256::from_big_endian(&RawU256::deserialize(buf)?),
+            block_gas_limit: U256::from_big_endian(&RawU256::deserialize(buf)?),
+        })
+    }
+}
+
+#[cfg(feature = "borsh")]
+impl BorshSerialize for MemoryAccount {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        u256_to_arr(&self.nonce).serialize(writer)?;
+        u256_to_arr(&self.balance).serialize(writer)?;
+        let storage: Vec<(RawH256, RawH256)> = self
+            .storage
+            .iter()
+            .map(|(key, value)| (key.0, value.0))
+            .collect();
+        storage.serialize(writer)?;
+        self.code.serialize(writer)
+    }
+}
+
+#[cfg(feature = "borsh")]
+impl BorshDeserialize for MemoryAccount {
+    fn deserialize(buf: &mut &[u8]) -> io::Result<Self> {
+        Ok(Self {
+            nonce: U256::from_big_endian(&RawU256::deserialize(buf)?),
+            balance: U256::from_big_endian(&RawU256::deserialize(buf)?),
+            storage: Vec::<(RawH256, RawH256)>::deserialize(buf)?
+                .into_iter()
+                .map(|(key, value)| (H256(key), H256(value)))
+                .collect(),
+            code: Vec::<u8>::deserialize(buf)?,
+        })
+    }
+}
+
 /// Memory backend, storing all state values in a `BTreeMap` in memory.
 #[derive(Clone)]
 pub struct MemoryBackend<'vicinity> {
@@ -69,6 +150,45 @@ impl<'vicinity> MemoryBackend<'vicinity> {
     pub fn state(&self) -> &BTreeMap<H160, MemoryAccount> {
         &self.state
     }
+
+    /// Ethereum compatible state root.
+    pub fn state_root(&self) -> H256 {
+        sec_trie_root(
+            self.state
+                .iter()
+                .map(|(address, account)| (address, encode_account(account))),
+        )
+    }
+
+    /// EIP-1186 proof of the account at `address` and its storage `keys`.
+    pub fn get_proof(&self, address: H160, keys: &[H256]) -> AccountProof {
+        let empty = MemoryAccount::default();
+        let account = self.state.get(&address).unwrap_or(&empty);
+        AccountProof {
+            address: address.0,
+            balance: u256_to_arr(&account.balance),
+            code_hash: keccak(&account.code).0,
+            nonce: u256_to_arr(&account.nonce),
+            storage_hash: storage_root(account.storage.iter()).0,
+            account_proof: sec_trie_proof(
+                self.state
+                    .iter()
+                    .map(|(address, account)| (address, encode_account(account))),
+                address.as_bytes(),
+            ),
+            storage_proof: storage_proofs(account.storage.iter(), keys),
+        }
+    }
+}
+
+/// RLP encoding of the account in the state trie.
+fn encode_account(account: &MemoryAccount) -> Vec<u8> {
+    account_rlp(
+        &account.nonce,
+        &account.balance,
+        &storage_root(account.storage.iter()),
+        &keccak(&account.code),
+    )
 }
 
 impl<'vicinity> Backend for MemoryBackend<'vicinity> {
@@ -219,3 +339,91 @@ impl<'vicinity> ApplyBackend for MemoryBackend<'vicinity> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trie::{verify_account_proof, ProofError};
+
+    #[test]
+    fn test_get_proof() {
+        let vicinity = MemoryVicinity::default();
+        let mut state = BTreeMap::new();
+        for i in 1..20u64 {
+            let storage = (0..i)
+                .map(|j| (H256::from_low_u64_be(j), H256::from_low_u64_be(i * j + 1)))
+                .collect();
+            let account = MemoryAccount {
+                nonce: i.into(),
+                balance: (i * 100).into(),
+                storage,
+                code: vec![i as u8],
+            };
+            state.insert(H160::from_low_u64_be(i), account);
+        }
+        let backend = MemoryBackend::new(&vicinity, state);
+        let root = backend.state_root();
+        let keys = [
+            H256::from_low_u64_be(0),
+            H256::from_low_u64_be(5),
+            H256::from_low_u64_be(100),
+        ];
+        // The last address has no account.
+        for i in 1..21 {
+            let proof = backend.get_proof(H160::from_low_u64_be(i), &keys);
+            assert_eq!(verify_account_proof(&root, &proof), Ok(()));
+        }
+
+        let proof = backend.get_proof(H160::from_low_u64_be(10), &keys);
+        assert_eq!(proof.nonce, u256_to_arr(&10.into()));
+        assert_eq!(proof.balance, u256_to_arr(&1000.into()));
+        assert_eq!(proof.code_hash, keccak(&[10]).0);
+        let values: Vec<U256> = proof
+            .storage_proof
+            .iter()
+            .map(|storage_proof| U256::from_big_endian(&storage_proof.value))
+            .collect();
+        assert_eq!(values, vec![1.into(), 51.into(), 0.into()]);
+
+        let mut forged = proof.clone();
+        forged.balance = u256_to_arr(&2000.into());
+        assert_eq!(
+            verify_account_proof(&root, &forged),
+            Err(ProofError::InvalidAccount)
+        );
+        let mut forged = proof.clone();
+        forged.storage_proof[1].value = u256_to_arr(&52.into());
+        assert_eq!(
+            verify_account_proof(&root, &forged),
+            Err(ProofError::InvalidStorage)
+        );
+        let mut forged = proof;
+        forged.address = H160::from_low_u64_be(11).0;
+        assert!(verify_account_proof(&root, &forged).is_err());
+    }
+
+    #[cfg(feature = "borsh")]
+    #[test]
+    fn test_borsh() {
+        let vicinity = MemoryVicinity {
+            chain_id: U256::one(),
+            block_hashes: vec![H256::repeat_byte(1)],
+            block_coinbase: H160::repeat_byte(2),
+            ..Default::default()
+        };
+        let bytes = vicinity.try_to_vec().unwrap();
+        assert_eq!(MemoryVicinity::try_from_slice(&bytes).unwrap(), vicinity);
+
+        let mut account = MemoryAccount {
+            nonce: 1.into(),
+            balance: 2.into(),
+            code: vec![0x60, 0x00],
+            ..Default::default()
+        };
+        account
+            .storage
+            .insert(H256::repeat_byte(3), H256::repeat_byte(4));
+        let bytes = account.try_to_vec().unwrap();
+        assert_eq!(MemoryAccount::try_from_slice(&bytes).unwrap(), account);
+    }
+}
@@ -2,8 +2,14 @@
 //!
 //! Backends store state information of the VM, and exposes it to runtime.
 
+mod cache;
+#[cfg(feature = "serde")]
+mod genesis;
 mod memory;
 
+pub use self::cache::CachedBackend;
+#[cfg(feature = "serde")]
+pub use self::genesis::{state_from_genesis_alloc, state_to_genesis_alloc};
 pub use self::memory::{MemoryAccount, MemoryBackend, MemoryVicinity};
 
 #[cfg(feature = "std")]
@@ -12,12 +18,22 @@ use std::vec::Vec;
 #[cfg(not(feature = "std"))]
 use alloc::vec::Vec;
 
+#[cfg(feature = "borsh")]
+use borsh::maybestd::io::{self, Write};
+#[cfg(feature = "borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
 use primitive_types::{H160, H256, U256};
 
+#[cfg(feature = "borsh")]
+use crate::types::{u256_to_arr, RawU256};
+
 /// Basic account information.
 #[derive(Clone, Eq, PartialEq, Debug, Default)]
-#[cfg_attr(feature = "with-codec", derive(codec::Encode, codec::Decode))]
-#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_crate::Serialize, serde_crate::Deserialize),
+    serde(crate = "serde_crate")
+)]
 pub struct Basic {
     /// Account balance.
     pub balance: U256,
@@ -25,6 +41,24 @@ pub struct Basic {
     pub nonce: U256,
 }
 
+#[cfg(feature = "borsh")]
+impl BorshSerialize for Basic {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        u256_to_arr(&self.balance).serialize(writer)?;
+        u256_to_arr(&self.nonce).serialize(writer)
+    }
+}
+
+#[cfg(feature = "borsh")]
+impl BorshDeserialize for Basic {
+    fn deserialize(buf: &mut &[u8]) -> io::Result<Self> {
+        Ok(Self {
+            balance: U256::from_big_endian(&RawU256::deserialize(buf)?),
+            nonce: U256::from_big_endian(&RawU256::deserialize(buf)?),
+        })
+    }
+}
+
 // pub use ethereum::Log;
 #[derive(Clone)]
 pub struct Log {
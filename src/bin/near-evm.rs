@@ -0,0 +1,302 @@
+//! Runs EVM bytecode locally against a JSON pre-state, e.g. to reproduce
+//! issues seen in a deployed contract. See `USAGE` for the options.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::process;
+
+use primitive_types::{H160, U256};
+use serde_json::Value;
+
+use near_evm::backend::{
+    state_from_genesis_alloc, state_to_genesis_alloc, MemoryAccount, MemoryBackend, MemoryVicinity,
+};
+use near_evm::runner::{CreateScheme, Runner, StackExecutor};
+use near_evm::tracer::{CallTracer, Eip3155Tracer, Tracer};
+use near_evm::types::{
+    bytes_to_hex, hex_to_bytes, log_to_event_json, ExitReason, TransactionStatus,
+};
+
+const USAGE: &str = "\
+Usage: near-evm [OPTIONS]
+
+Runs a call to --address, or with --create deploys the code, and prints the
+return data, exit reason and logs, the latter as NEAR `EVENT_JSON` lines.
+Exits with 1 unless it succeeded. EVM gas is not metered, so traces have gas
+costs and gas used of 0.
+
+Options:
+    --code <HEX>          Code to run, instead of the code of --address in
+                          the pre-state
+    --code-file <PATH>    Code to run from a file, in hex as output by solc
+                          or raw bytes
+    --input <HEX>         Call data, or constructor arguments with --create
+    --value <QUANTITY>    Value to transfer, decimal or 0x prefixed hex
+    --caller <ADDRESS>    Sender and origin [default: 0x00..01]
+    --address <ADDRESS>   Account running the code [default: 0x00..02]
+    --prestate <PATH>     Geth genesis `alloc` JSON of the state to run on
+    --chain-id <QUANTITY> [default: 1313161554]
+    --create              Run the code as init code of a new contract
+    --trace               Write an EIP-3155 trace of every step to stderr
+    --call-trace          Print the call tree in geth `callTracer` format
+    --dump                Print the state after execution as `alloc` JSON
+    -h, --help            Print this message";
+
+/// Parsed command line.
+struct Options {
+    code: Option<Vec<u8>>,
+    input: Vec<u8>,
+    value: U256,
+    caller: H160,
+    address: H160,
+    prestate: Option<String>,
+    chain_id: U256,
+    create: bool,
+    trace: bool,
+    call_trace: bool,
+    dump: bool,
+}
+
+impl Options {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Self, String> {
+        let mut options = Options {
+            code: None,
+            input: Vec::new(),
+            value: U256::zero(),
+            caller: H160::from_low_u64_be(1),
+            address: H160::from_low_u64_be(2),
+            prestate: None,
+            chain_id: U256::from(1313161554),
+            create: false,
+            trace: false,
+            call_trace: false,
+            dump: false,
+        };
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("missing value of {}", arg));
+            match arg.as_str() {
+                "--code" => options.code = Some(parse_hex(&value()?)?),
+                "--code-file" => {
+                    let path = value()?;
+                    let code = fs::read(&path).map_err(|e| format!("{}: {}", path, e))?;
+                    options.code = Some(match std::str::from_utf8(&code) {
+                        Ok(text) => parse_hex(text.trim())?,
+                        Err(_) => code,
+                    });
+                }
+                "--input" => options.input = parse_hex(&value()?)?,
+                "--value" => options.value = parse_quantity(&value()?)?,
+                "--caller" => options.caller = parse_address(&value()?)?,
+                "--address" => options.address = parse_address(&value()?)?,
+                "--prestate" => options.prestate = Some(value()?),
+                "--chain-id" => options.chain_id = parse_quantity(&value()?)?,
+                "--create" => options.create = true,
+                "--trace" => options.trace = true,
+                "--call-trace" => options.call_trace = true,
+                "--dump" => options.dump = true,
+                _ => return Err(format!("unknown option {}", arg)),
+            }
+        }
+        if options.trace && options.call_trace {
+            return Err(String::from(
+                "--trace and --call-trace can't be used together",
+            ));
+        }
+        if options.create && options.code.is_none() {
+            return Err(String::from("--create needs --code or --code-file"));
+        }
+        Ok(options)
+    }
+}
+
+fn parse_hex(value: &str) -> Result<Vec<u8>, String> {
+    hex_to_bytes(value.trim_start_matches("0x")).ok_or(format!("invalid hex {}", value))
+}
+
+fn parse_address(value: &str) -> Result<H160, String> {
+    match parse_hex(value)? {
+        ref bytes if bytes.len() == 20 => Ok(H160::from_slice(bytes)),
+        _ => Err(format!("invalid address {}", value)),
+    }
+}
+
+fn parse_quantity(value: &str) -> Result<U256, String> {
+    let result = if value.starts_with("0x") {
+        U256::from_str_radix(&value[2..], 16).ok()
+    } else {
+        U256::from_dec_str(value).ok()
+    };
+    result.ok_or(format!("invalid quantity {}", value))
+}
+
+fn load_state(options: &Options) -> Result<BTreeMap<H160, MemoryAccount>, String> {
+    let mut state = match &options.prestate {
+        Some(path) => {
+            let json = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+            let alloc: Value =
+                serde_json::from_str(&json).map_err(|e| format!("{}: {}", path, e))?;
+            state_from_genesis_alloc(&alloc).map_err(|e| format!("{}: {}", path, e))?
+        }
+        None => BTreeMap::new(),
+    };
+    if let (Some(code), false) = (&options.code, options.create) {
+        state.entry(options.address).or_default().code = code.clone();
+    }
+    Ok(state)
+}
+
+/// Runs the call or create, returning the address of the created contract.
+fn transact(
+    executor: &mut StackExecutor<MemoryBackend>,
+    options: &Options,
+) -> (ExitReason, (Vec<u8>, Option<H160>)) {
+    if options.create {
+        let address = executor.create_address(CreateScheme::Legacy {
+            caller: options.caller,
+        });
+        let mut init_code = options.code.clone().unwrap_or_default();
+        init_code.extend_from_slice(&options.input);
+        let (reason, result) = executor.transact_create(options.caller, options.value, init_code);
+        (reason, (result, Some(address)))
+    } else {
+        let (reason, result) = executor.transact_call(
+            options.caller,
+            options.address,
+            options.value,
+            options.input.clone(),
+        );
+        (reason, (result, None))
+    }
+}
+
+fn run(options: Options) -> Result<bool, String> {
+    let vicinity = MemoryVicinity {
+        origin: options.caller,
+        chain_id: options.chain_id,
+        ..Default::default()
+    };
+    let mut backend = MemoryBackend::new(&vicinity, load_state(&options)?);
+    let mut eip3155_tracer = Eip3155Tracer::new(io::stderr());
+    let mut call_tracer = CallTracer::new();
+    let tracer: Option<&mut dyn Tracer> = if options.trace {
+        Some(&mut eip3155_tracer)
+    } else if options.call_trace {
+        Some(&mut call_tracer)
+    } else {
+        None
+    };
+    let execution = Runner::execute(
+        &mut backend,
+        options.value,
+        true,
+        false,
+        tracer,
+        |executor| transact(executor, &options),
+    );
+    eip3155_tracer.into_inner().map_err(|e| e.to_string())?;
+    let call_trace = call_tracer.into_root().map(|root| root.to_json());
+    let address = execution.result.1;
+    let execution = execution.map_result(|(result, _)| result);
+
+    println!("return: 0x{}", bytes_to_hex(&execution.result));
+    println!("exit: {:?}", execution.reason);
+    match execution.status() {
+        TransactionStatus::Succeed => (),
+        TransactionStatus::Revert(message) => println!("revert: {}", message),
+        TransactionStatus::Error(error) => println!("error: {}", error.message()),
+    }
+    if let (Some(address), true) = (address, execution.reason.is_succeed()) {
+        println!("contract: 0x{}", bytes_to_hex(&address.0));
+    }
+    for (index, log) in execution.logs.iter().enumerate() {
+        println!("{}", log_to_event_json(log, index as u32));
+    }
+    if let Some(call_trace) = call_trace {
+        println!("call trace: {}", call_trace);
+    }
+    if options.dump {
+        println!("state: {}", state_to_genesis_alloc(backend.state()));
+    }
+    Ok(execution.reason.is_succeed())
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return;
+    }
+    let options = match Options::parse(args.into_iter()) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("error: {}\n\n{}", error, USAGE);
+            process::exit(2);
+        }
+    };
+    match run(options) {
+        Ok(true) => (),
+        Ok(false) => process::exit(1),
+        Err(error) => {
+            eprintln!("error: {}", error);
+            process::exit(2);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let options = parse(&[]).unwrap();
+        assert_eq!(options.code, None);
+        assert_eq!(options.caller, H160::from_low_u64_be(1));
+        assert_eq!(options.address, H160::from_low_u64_be(2));
+        assert_eq!(options.chain_id, U256::from(1313161554));
+        assert!(!options.create && !options.trace && !options.call_trace && !options.dump);
+
+        let options = parse(&[
+            "--code",
+            "0x6001",
+            "--input",
+            "abcd",
+            "--value",
+            "0x10",
+            "--caller",
+            "0x00000000000000000000000000000000000000aa",
+            "--chain-id",
+            "42",
+            "--create",
+            "--call-trace",
+            "--dump",
+        ])
+        .unwrap();
+        assert_eq!(options.code, Some(vec![0x60, 0x01]));
+        assert_eq!(options.input, vec![0xab, 0xcd]);
+        assert_eq!(options.value, U256::from(16));
+        assert_eq!(options.caller, H160::from_low_u64_be(0xaa));
+        assert_eq!(options.chain_id, U256::from(42));
+        assert!(options.create && options.call_trace && options.dump);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |args: &[&str]| parse(args).err().unwrap();
+        assert_eq!(error(&["--verbose"]), "unknown option --verbose");
+        assert_eq!(error(&["--input"]), "missing value of --input");
+        assert_eq!(error(&["--input", "0xzz"]), "invalid hex 0xzz");
+        assert_eq!(error(&["--caller", "0x01"]), "invalid address 0x01");
+        assert_eq!(error(&["--value", "ten"]), "invalid quantity ten");
+        assert_eq!(
+            error(&["--trace", "--call-trace"]),
+            "--trace and --call-trace can't be used together"
+        );
+        assert_eq!(error(&["--create"]), "--create needs --code or --code-file");
+    }
+}
@@ -0,0 +1,316 @@
+//! # NEP-141 connector
+//!
+//! Exposes NEAR fungible tokens inside the EVM as ERC-20 contracts. Tokens sent
+//! to the EVM account via `ft_transfer_call` are minted in the connected ERC-20
+//! contract, and burning them there with a call to `EXIT_FT_TO_NEAR_ADDRESS`
+//! sends them back with `ft_transfer`.
+
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec::Vec};
+#[cfg(feature = "std")]
+use std::{string::String, vec::Vec};
+
+use primitive_types::{H160, U256};
+
+use crate::types::{hex_digit, u128_to_dec, u256_to_arr};
+
+/// Selector of `mint(address,uint256)`.
+const MINT_SELECTOR: [u8; 4] = [0x40, 0xc1, 0x0f, 0x19];
+
+/// Arguments of NEP-141 `ft_on_transfer`.
+pub struct FtOnTransferArgs {
+    pub sender_id: Vec<u8>,
+    pub amount: U256,
+    /// EVM address to mint tokens to, passed in hex as `msg`.
+    pub recipient: H160,
+}
+
+impl FtOnTransferArgs {
+    /// Parses JSON arguments, e.g.
+    /// `{"sender_id": "alice.near", "amount": "100", "msg": "0x1234..."}`.
+    /// Keys may come in any order, and other fields are ignored.
+    pub fn parse(input: &[u8]) -> Option<Self> {
+        let mut sender_id = None;
+        let mut amount = None;
+        let mut msg = None;
+        let mut reader = JsonReader { input, position: 0 };
+        reader.expect(b'{')?;
+        if !reader.eat(b'}') {
+            loop {
+                let key = reader.string()?;
+                reader.expect(b':')?;
+                match key.as_str() {
+                    "sender_id" => sender_id = Some(reader.string()?),
+                    "amount" => amount = Some(reader.string()?),
+                    "msg" => msg = Some(reader.string()?),
+                    _ => reader.skip_value()?,
+                }
+                if !reader.eat(b',') {
+                    reader.expect(b'}')?;
+                    break;
+                }
+            }
+        }
+        reader.end()?;
+
+        let sender_id = sender_id?.into_bytes();
+        let amount = U256::from_dec_str(&amount?).ok()?;
+        let msg = msg?;
+        let msg = msg.as_bytes();
+        let msg = if msg.starts_with(b"0x") {
+            &msg[2..]
+        } else {
+            msg
+        };
+        if msg.len() != 40 {
+            return None;
+        }
+        let mut recipient = H160::zero();
+        for (i, byte) in recipient.0.iter_mut().enumerate() {
+            *byte = (hex_digit(msg[2 * i])? << 4) | hex_digit(msg[2 * i + 1])?;
+        }
+        Some(Self {
+            sender_id,
+            amount,
+            recipient,
+        })
+    }
+}
+
+/// Reads JSON just far enough for `FtOnTransferArgs`: strings, and any other
+/// value to skip it. Whitespace before each token is skipped.
+struct JsonReader<'a> {
+    input: &'a [u8],
+    position: usize,
+}
+
+impl<'a> JsonReader<'a> {
+    fn peek(&mut self) -> Option<u8> {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') =
+            self.input.get(self.position)
+        {
+            self.position += 1;
+        }
+        self.input.get(self.position).copied()
+    }
+
+    /// Consumes `byte` if it is the next token.
+    fn eat(&mut self, byte: u8) -> bool {
+        let found = self.peek() == Some(byte);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, byte: u8) -> Option<()> {
+        if self.eat(byte) {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    /// Fails unless only whitespace is left.
+    fn end(&mut self) -> Option<()> {
+        match self.peek() {
+            None => Some(()),
+            Some(_) => None,
+        }
+    }
+
+    fn next_byte(&mut self) -> Option<u8> {
+        let byte = *self.input.get(self.position)?;
+        self.position += 1;
+        Some(byte)
+    }
+
+    /// The four hex digits of a `\u` escape.
+    fn code_unit(&mut self) -> Option<u32> {
+        let mut result = 0;
+        for _ in 0..4 {
+            result = (result << 4) | hex_digit(self.next_byte()?)? as u32;
+        }
+        Some(result)
+    }
+
+    /// A string, with its escapes decoded.
+    fn string(&mut self) -> Option<String> {
+        self.expect(b'"')?;
+        let mut result = Vec::new();
+        loop {
+            match self.next_byte()? {
+                b'"' => return String::from_utf8(result).ok(),
+                b'\\' => {
+                    let byte = match self.next_byte()? {
+                        byte @ b'"' | byte @ b'\\' | byte @ b'/' => byte,
+                        b'b' => 0x08,
+                        b'f' => 0x0c,
+                        b'n' => b'\n',
+                        b'r' => b'\r',
+                        b't' => b'\t',
+                        b'u' => {
+                            let mut code = self.code_unit()?;
+                            if (0xd800..0xdc00).contains(&code) {
+                                self.expect_bytes(b"\\u")?;
+                                let low = self.code_unit()?;
+                                if !(0xdc00..0xe000).contains(&low) {
+                                    return None;
+                                }
+                                code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                            }
+                            let c = core::char::from_u32(code)?;
+                            result.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                            continue;
+                        }
+                        _ => return None,
+                    };
+                    result.push(byte);
+                }
+                byte if byte < 0x20 => return None,
+                byte => result.push(byte),
+            }
+        }
+    }
+
+    fn expect_bytes(&mut self, bytes: &[u8]) -> Option<()> {
+        for byte in bytes {
+            if self.next_byte()? != *byte {
+                return None;
+            }
+        }
+        Some(())
+    }
+
+    /// Skips a value of any type. Nesting is tracked without recursion, and
+    /// numbers and literals are only checked for their characters.
+    fn skip_value(&mut self) -> Option<()> {
+        let mut closers = Vec::new();
+        loop {
+            match self.peek()? {
+                b'"' => {
+                    self.string()?;
+                }
+                b'{' => {
+                    self.position += 1;
+                    closers.push(b'}');
+                    if !self.eat(b'}') {
+                        self.string()?;
+                        self.expect(b':')?;
+                        continue;
+                    }
+                    closers.pop();
+                }
+                b'[' => {
+                    self.position += 1;
+                    closers.push(b']');
+                    if !self.eat(b']') {
+                        continue;
+                    }
+                    closers.pop();
+                }
+                _ => {
+                    let start = self.position;
+                    while let Some(b'0'..=b'9') | Some(b'a'..=b'z') | Some(b'+') | Some(b'-')
+                    | Some(b'.') | Some(b'E') = self.input.get(self.position)
+                    {
+                        self.position += 1;
+                    }
+                    if self.position == start {
+                        return None;
+                    }
+                }
+            }
+            // After a value: close the containers it ends, or go on to the
+            // next element.
+            loop {
+                let closer = match closers.last() {
+                    Some(closer) => *closer,
+                    None => return Some(()),
+                };
+                if self.eat(closer) {
+                    closers.pop();
+                    continue;
+                }
+                self.expect(b',')?;
+                if closer == b'}' {
+                    self.string()?;
+                    self.expect(b':')?;
+                }
+                break;
+            }
+        }
+    }
+}
+
+/// ABI-encoded call of `mint(recipient, amount)`.
+pub fn mint_input(recipient: &H160, amount: &U256) -> Vec<u8> {
+    let mut result = Vec::with_capacity(4 + 32 + 32);
+    result.extend_from_slice(&MINT_SELECTOR);
+    result.extend_from_slice(&[0u8; 12]);
+    result.extend_from_slice(&recipient.0);
+    result.extend_from_slice(&u256_to_arr(amount));
+    result
+}
+
+/// JSON arguments of NEP-141 `ft_transfer`.
+pub fn ft_transfer_args(receiver_id: &[u8], amount: u128) -> Vec<u8> {
+    let mut result = Vec::new();
+    result.extend_from_slice(b"{\"receiver_id\":\"");
+    result.extend_from_slice(receiver_id);
+    result.extend_from_slice(b"\",\"amount\":\"");
+    result.extend_from_slice(u128_to_dec(amount).as_bytes());
+    result.extend_from_slice(b"\"}");
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ft_on_transfer_args() {
+        let args = FtOnTransferArgs::parse(
+            br#"{"sender_id": "alice.near", "amount": "1000", "msg": "0x00000000000000000000000000000000000000Ff"}"#,
+        )
+        .unwrap();
+        assert_eq!(args.sender_id, b"alice.near".to_vec());
+        assert_eq!(args.amount, U256::from(1000));
+        assert_eq!(args.recipient, H160::from_low_u64_be(255));
+        let args = FtOnTransferArgs::parse(
+            br#"{"msg":"\u0030x00000000000000000000000000000000000000ff","amount":"1","sender_id":"a\"b"}"#,
+        )
+        .unwrap();
+        assert_eq!(args.sender_id, b"a\"b".to_vec());
+        assert_eq!(args.recipient, H160::from_low_u64_be(255));
+        assert!(
+            FtOnTransferArgs::parse(br#"{"sender_id":"a.near","amount":"1","msg":""}"#).is_none()
+        );
+        let args = FtOnTransferArgs::parse(
+            br#" { "memo" : null, "x": [1, {"y": [true, -1.5e3, "]"]}, {}], "amount": "2",
+            "sender_id": "caf\u00e9 \ud83d\ude00", "msg": "00000000000000000000000000000000000000ff" } "#,
+        )
+        .unwrap();
+        assert_eq!(args.sender_id, "caf\u{e9} \u{1f600}".as_bytes().to_vec());
+        assert_eq!(args.amount, U256::from(2));
+        for input in [
+            &br#"{"sender_id":"a","amount":"1"}"#[..],
+            br#"{"sender_id":"a","amount":1,"msg":"00000000000000000000000000000000000000ff"}"#,
+            br#"{"sender_id":"a","amount":"1","msg":"00000000000000000000000000000000000000ff"} x"#,
+            br#"{"sender_id":"a","amount":"1","msg":"00000000000000000000000000000000000000ff","x":[}"#,
+            br#"{"sender_id":"\ud83d","amount":"1","msg":"00000000000000000000000000000000000000ff"}"#,
+        ]
+        .iter()
+        {
+            assert!(FtOnTransferArgs::parse(input).is_none());
+        }
+    }
+
+    #[test]
+    fn test_ft_transfer_args() {
+        assert_eq!(
+            ft_transfer_args(b"bob.near", 1234567890123456789),
+            br#"{"receiver_id":"bob.near","amount":"1234567890123456789"}"#.to_vec()
+        );
+    }
+}
@@ -0,0 +1,445 @@
+//! # Disassembler
+//!
+//! Instruction listings of EVM bytecode, jump destination analysis, and
+//! splitting of solc output into constructor, runtime code and the CBOR
+//! metadata trailer.
+
+#[cfg(not(feature = "std"))]
+use alloc::{format, string::String, vec::Vec};
+#[cfg(feature = "std")]
+use std::{format, string::String, vec::Vec};
+
+use core::fmt;
+use core::str;
+
+pub use crate::evm_core::{mnemonic, ExternalOpcode, Opcode, Valids};
+use crate::types::bytes_to_hex;
+
+const JUMPDEST: u8 = 0x5b;
+const CODECOPY: u8 = 0x39;
+/// Keys solc writes to metadata trailers, one of which a trailer must have.
+const METADATA_KEYS: [&str; 4] = ["ipfs", "bzzr0", "bzzr1", "solc"];
+/// Nesting of CBOR arrays and maps accepted in a metadata trailer, which
+/// bounds the recursion on untrusted code.
+const MAX_CBOR_DEPTH: usize = 8;
+
+/// An instruction of some bytecode.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Instruction<'a> {
+    pub offset: usize,
+    pub opcode: u8,
+    /// Immediate of a `PUSH`, shorter than its size if truncated by the end
+    /// of the code.
+    pub immediate: &'a [u8],
+}
+
+impl<'a> Instruction<'a> {
+    /// Mnemonic of the opcode, `None` for undefined opcodes.
+    pub fn name(&self) -> Option<&'static str> {
+        mnemonic(self.opcode)
+    }
+
+    pub fn is_jumpdest(&self) -> bool {
+        self.opcode == JUMPDEST
+    }
+
+    /// Size of the immediate of a `PUSH`, zero for other opcodes.
+    pub fn immediate_size(&self) -> usize {
+        match Opcode::parse(self.opcode) {
+            Ok(Opcode::Push(size)) => size as usize,
+            _ => 0,
+        }
+    }
+
+    /// Whether the immediate of a `PUSH` runs past the end of the code.
+    pub fn is_truncated(&self) -> bool {
+        self.immediate.len() < self.immediate_size()
+    }
+
+    /// Value pushed by a `PUSH` if it fits in `usize`, missing bytes of a
+    /// truncated immediate being zeros as when executed.
+    pub fn push_value(&self) -> Option<usize> {
+        let size = self.immediate_size();
+        if size == 0 {
+            return None;
+        }
+        let mut value: usize = 0;
+        for i in 0..size {
+            let byte = self.immediate.get(i).cloned().unwrap_or(0);
+            if value.leading_zeros() < 8 {
+                return None;
+            }
+            value = (value << 8) | byte as usize;
+        }
+        Some(value)
+    }
+}
+
+impl<'a> fmt::Display for Instruction<'a> {
+    /// Formats as e.g. `0005: PUSH1 0x80`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04x}: ", self.offset)?;
+        match self.name() {
+            Some(name) => write!(f, "{}", name)?,
+            None => write!(f, "UNKNOWN(0x{:02x})", self.opcode)?,
+        }
+        if self.immediate_size() > 0 {
+            write!(f, " 0x{}", bytes_to_hex(self.immediate))?;
+            if self.is_truncated() {
+                write!(f, " (truncated)")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Iterator over the instructions of some bytecode.
+pub struct Instructions<'a> {
+    code: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Iterator for Instructions<'a> {
+    type Item = Instruction<'a>;
+
+    fn next(&mut self) -> Option<Instruction<'a>> {
+        let offset = self.offset;
+        let opcode = *self.code.get(offset)?;
+        let size = match Opcode::parse(opcode) {
+            Ok(Opcode::Push(size)) => size as usize,
+            _ => 0,
+        };
+        let start = offset + 1;
+        let end = (start + size).min(self.code.len());
+        self.offset = start + size;
+        Some(Instruction {
+            offset,
+            opcode,
+            immediate: &self.code[start..end],
+        })
+    }
+}
+
+/// Instructions of `code`, skipping over `PUSH` immediates the same way the
+/// interpreter does.
+pub fn instructions(code: &[u8]) -> Instructions<'_> {
+    Instructions { code, offset: 0 }
+}
+
+/// Offsets of the valid jump destinations of `code`, i.e. the `JUMPDEST`s
+/// outside of `PUSH` immediates.
+pub fn jumpdests(code: &[u8]) -> Vec<usize> {
+    instructions(code)
+        .filter(Instruction::is_jumpdest)
+        .map(|instruction| instruction.offset)
+        .collect()
+}
+
+/// Listing of `code` with an instruction per line. A metadata trailer is
+/// listed as a single line instead of being decoded as instructions.
+pub fn disassemble(code: &[u8]) -> String {
+    let (code, metadata) = split_metadata(code);
+    let mut result = String::new();
+    for instruction in instructions(code) {
+        result.push_str(&format!("{}\n", instruction));
+    }
+    if let Some(metadata) = metadata {
+        result.push_str(&format!(
+            "{:04x}: metadata 0x{}\n",
+            code.len(),
+            bytes_to_hex(metadata.data)
+        ));
+    }
+    result
+}
+
+/// Value in a solc metadata trailer.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MetadataValue<'a> {
+    Bytes(&'a [u8]),
+    Text(&'a str),
+    Bool(bool),
+    /// Any other CBOR item, encoded.
+    Other(&'a [u8]),
+}
+
+/// CBOR map solc appends to runtime code, e.g. with the `ipfs` hash of the
+/// contract metadata and the `solc` version.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Metadata<'a> {
+    /// The whole trailer, including the two bytes of its length.
+    pub data: &'a [u8],
+    pub entries: Vec<(&'a str, MetadataValue<'a>)>,
+}
+
+impl<'a> Metadata<'a> {
+    pub fn get(&self, key: &str) -> Option<MetadataValue<'a>> {
+        self.entries
+            .iter()
+            .find(|(entry_key, _)| *entry_key == key)
+            .map(|(_, value)| *value)
+    }
+
+    /// Version of the compiler, for release builds of solc 0.5.9 and later.
+    pub fn solc_version(&self) -> Option<(u8, u8, u8)> {
+        match self.get("solc")? {
+            MetadataValue::Bytes(&[major, minor, patch]) => Some((major, minor, patch)),
+            _ => None,
+        }
+    }
+}
+
+/// Splits `code` into the code itself and its metadata trailer, if it ends
+/// with one. The trailer is a CBOR map with at least one of the keys solc
+/// writes, followed by its length as two big endian bytes.
+pub fn split_metadata(code: &[u8]) -> (&[u8], Option<Metadata<'_>>) {
+    if code.len() < 2 {
+        return (code, None);
+    }
+    let size = ((code[code.len() - 2] as usize) << 8) | code[code.len() - 1] as usize;
+    if size == 0 || size + 2 > code.len() {
+        return (code, None);
+    }
+    let start = code.len() - 2 - size;
+    match decode_metadata(&code[start..code.len() - 2]) {
+        Some(entries) => (
+            &code[..start],
+            Some(Metadata {
+                data: &code[start..],
+                entries,
+            }),
+        ),
+        None => (code, None),
+    }
+}
+
+/// Entries of `data` if it is exactly one CBOR map with text keys, one of
+/// which is a known metadata key.
+fn decode_metadata(data: &[u8]) -> Option<Vec<(&str, MetadataValue<'_>)>> {
+    if data[0] >> 5 != 5 {
+        return None;
+    }
+    let (count, mut offset) = cbor_head(data)?;
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        let key = match cbor_item(data.get(offset..)?, 1)? {
+            (MetadataValue::Text(key), size) => {
+                offset += size;
+                key
+            }
+            _ => return None,
+        };
+        let (value, size) = cbor_item(data.get(offset..)?, 1)?;
+        offset += size;
+        entries.push((key, value));
+    }
+    if offset != data.len() {
+        return None;
+    }
+    if !entries.iter().any(|(key, _)| METADATA_KEYS.contains(key)) {
+        return None;
+    }
+    Some(entries)
+}
+
+/// Argument of the CBOR item at the start of `data`, with the size of its
+/// head. Indefinite lengths are not supported.
+fn cbor_head(data: &[u8]) -> Option<(u64, usize)> {
+    let info = *data.first()? & 0x1f;
+    let size = match info {
+        0..=23 => return Some((info as u64, 1)),
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        _ => return None,
+    };
+    let bytes = data.get(1..1 + size)?;
+    let value = bytes
+        .iter()
+        .fold(0u64, |value, byte| (value << 8) | *byte as u64);
+    Some((value, 1 + size))
+}
+
+/// The CBOR item at the start of `data`, with its size.
+fn cbor_item(data: &[u8], depth: usize) -> Option<(MetadataValue<'_>, usize)> {
+    let (argument, head) = cbor_head(data)?;
+    let major = data[0] >> 5;
+    match major {
+        // Unsigned and negative integers.
+        0 | 1 => Some((MetadataValue::Other(&data[..head]), head)),
+        // Byte and text strings.
+        2 | 3 => {
+            if argument > (data.len() - head) as u64 {
+                return None;
+            }
+            let end = head + argument as usize;
+            let bytes = &data[head..end];
+            let value = if major == 2 {
+                MetadataValue::Bytes(bytes)
+            } else {
+                MetadataValue::Text(str::from_utf8(bytes).ok()?)
+            };
+            Some((value, end))
+        }
+        // Arrays and maps.
+        4 | 5 => {
+            if depth >= MAX_CBOR_DEPTH {
+                return None;
+            }
+            let items = if major == 4 {
+                argument
+            } else {
+                argument.saturating_mul(2)
+            };
+            let mut end = head;
+            for _ in 0..items {
+                end += cbor_item(data.get(end..)?, depth + 1)?.1;
+            }
+            Some((MetadataValue::Other(&data[..end]), end))
+        }
+        // Simple values and floats.
+        7 => match data[0] {
+            0xf4 => Some((MetadataValue::Bool(false), 1)),
+            0xf5 => Some((MetadataValue::Bool(true), 1)),
+            _ => Some((MetadataValue::Other(&data[..head]), head)),
+        },
+        _ => None,
+    }
+}
+
+/// Parts of contract creation code as output by solc.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct CreationCode<'a> {
+    /// Code run on deployment, which returns the runtime code.
+    pub constructor: &'a [u8],
+    /// Code of the deployed contract, with its metadata trailer.
+    pub runtime: &'a [u8],
+    /// Whatever follows the runtime code, e.g. ABI encoded constructor
+    /// arguments.
+    pub arguments: &'a [u8],
+}
+
+/// Splits creation `code` into constructor and runtime code.
+///
+/// The runtime code is found from the first `CODECOPY` of the constructor
+/// copying from past itself with constant offset and size, as in the
+/// `PUSH size DUP1 PUSH offset PUSH 0 CODECOPY` emitted by solc. Returns
+/// `None` if there is no such `CODECOPY`.
+pub fn split_creation_code(code: &[u8]) -> Option<CreationCode<'_>> {
+    // Values known to be on top of the stack, top last.
+    let mut stack: Vec<Option<usize>> = Vec::new();
+    for instruction in instructions(code) {
+        match instruction.opcode {
+            // PUSH1 to PUSH32.
+            0x60..=0x7f => stack.push(instruction.push_value()),
+            // DUP1 to DUP16.
+            0x80..=0x8f => {
+                let depth = (instruction.opcode - 0x80) as usize + 1;
+                let value = if depth <= stack.len() {
+                    stack[stack.len() - depth]
+                } else {
+                    None
+                };
+                stack.push(value);
+            }
+            CODECOPY => {
+                let len = stack.len();
+                if len >= 3 {
+                    if let (Some(offset), Some(size)) = (stack[len - 2], stack[len - 3]) {
+                        let end = offset.checked_add(size)?;
+                        if offset > instruction.offset && end <= code.len() {
+                            return Some(CreationCode {
+                                constructor: &code[..offset],
+                                runtime: &code[offset..end],
+                                arguments: &code[end..],
+                            });
+                        }
+                    }
+                }
+                stack.clear();
+            }
+            _ => stack.clear(),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::hex_to_bytes;
+
+    #[test]
+    fn test_disassemble() {
+        // Runtime code: PUSH1 0x04 JUMP PUSH1 0x5b JUMPDEST STOP, then a
+        // trailer of {"solc": 0x000811}.
+        let mut runtime = hex_to_bytes("600456605b5b00").unwrap();
+        let metadata = hex_to_bytes("a164736f6c6343000811000a").unwrap();
+        runtime.extend_from_slice(&metadata);
+        // Constructor: PUSH1 size DUP1 PUSH1 offset PUSH1 0 CODECOPY PUSH1 0
+        // RETURN INVALID.
+        let mut code = vec![0x60, runtime.len() as u8, 0x80, 0x60, 12, 0x60, 0x00];
+        code.extend_from_slice(&[0x39, 0x60, 0x00, 0xf3, 0xfe]);
+        code.extend_from_slice(&runtime);
+        code.extend_from_slice(&[0xab; 32]);
+
+        let creation = split_creation_code(&code).unwrap();
+        assert_eq!(creation.constructor.len(), 12);
+        assert_eq!(creation.runtime, &runtime[..]);
+        assert_eq!(creation.arguments, &[0xab; 32][..]);
+
+        let (runtime_code, trailer) = split_metadata(creation.runtime);
+        assert_eq!(runtime_code.len(), 7);
+        let trailer = trailer.unwrap();
+        assert_eq!(trailer.data, &metadata[..]);
+        assert_eq!(trailer.solc_version(), Some((0, 8, 17)));
+        assert_eq!(split_metadata(runtime_code).1, None);
+
+        assert_eq!(jumpdests(creation.runtime), vec![5]);
+        assert!(Valids::new(creation.runtime).is_valid(5));
+        assert!(!Valids::new(creation.runtime).is_valid(4));
+        assert_eq!(
+            disassemble(creation.runtime),
+            "0000: PUSH1 0x04\n\
+             0002: JUMP\n\
+             0003: PUSH1 0x5b\n\
+             0005: JUMPDEST\n\
+             0006: STOP\n\
+             0007: metadata 0xa164736f6c6343000811000a\n"
+        );
+        assert_eq!(
+            disassemble(&[0x0c, 0x61, 0x01]),
+            "0000: UNKNOWN(0x0c)\n0001: PUSH2 0x01 (truncated)\n"
+        );
+    }
+
+    #[test]
+    fn test_split_metadata() {
+        // An empty map, or a map without any key solc writes.
+        let code = hex_to_bytes("6000a00001").unwrap();
+        assert_eq!(split_metadata(&code), (&code[..], None));
+        let code = hex_to_bytes("6000a1616101000004").unwrap();
+        assert_eq!(split_metadata(&code), (&code[..], None));
+
+        // {"ipfs": h'01', "x": [[0]]}
+        let code = hex_to_bytes("6000a2646970667341016178818100000d").unwrap();
+        let (runtime, metadata) = split_metadata(&code);
+        assert_eq!(runtime, &code[..2]);
+        let metadata = metadata.unwrap();
+        assert_eq!(metadata.get("ipfs"), Some(MetadataValue::Bytes(&[1])));
+        assert_eq!(
+            metadata.get("x"),
+            Some(MetadataValue::Other(&[0x81, 0x81, 0]))
+        );
+
+        // {"solc": [[...[0]...]]}, nested far deeper than a trailer is, is
+        // rejected without recursing through every level.
+        let mut trailer = hex_to_bytes("a164736f6c63").unwrap();
+        trailer.resize(0xfff0, 0x81);
+        trailer.push(0x00);
+        let mut code = trailer.clone();
+        code.extend_from_slice(&(trailer.len() as u16).to_be_bytes());
+        assert_eq!(split_metadata(&code), (&code[..], None));
+    }
+}
@@ -1,9 +1,11 @@
 use crate::evm_core::ExternalOpcode;
 #[cfg(not(feature = "std"))]
-use alloc::borrow::Cow;
+use alloc::{borrow::Cow, format};
 
 #[cfg(feature = "std")]
-use std::borrow::Cow;
+use std::{borrow::Cow, format};
+
+use borsh::{BorshDeserialize, BorshSerialize};
 
 pub trait ToStr {
     fn to_str(self) -> &'static str;
@@ -23,9 +25,12 @@ pub enum Capture<E, T> {
 }
 
 /// Exit reason.
-#[derive(Clone, Debug, Eq, PartialEq)]
-#[cfg_attr(feature = "with-codec", derive(codec::Encode, codec::Decode))]
-#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, Eq, PartialEq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_crate::Serialize, serde_crate::Deserialize),
+    serde(crate = "serde_crate")
+)]
 pub enum ExitReason {
     /// Machine has succeeded.
     Succeed(ExitSucceed),
@@ -73,9 +78,12 @@ impl ExitReason {
 }
 
 /// Exit succeed reason.
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
-#[cfg_attr(feature = "with-codec", derive(codec::Encode, codec::Decode))]
-#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_crate::Serialize, serde_crate::Deserialize),
+    serde(crate = "serde_crate")
+)]
 pub enum ExitSucceed {
     /// Machine encountered an explict stop.
     Stopped,
@@ -92,9 +100,12 @@ impl From<ExitSucceed> for ExitReason {
 }
 
 /// Exit revert reason.
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
-#[cfg_attr(feature = "with-codec", derive(codec::Encode, codec::Decode))]
-#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_crate::Serialize, serde_crate::Deserialize),
+    serde(crate = "serde_crate")
+)]
 pub enum ExitRevert {
     /// Machine encountered an explict revert.
     Reverted,
@@ -107,9 +118,12 @@ impl From<ExitRevert> for ExitReason {
 }
 
 /// Exit error reason.
-#[derive(Clone, Debug, Eq, PartialEq)]
-#[cfg_attr(feature = "with-codec", derive(codec::Encode, codec::Decode))]
-#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, Eq, PartialEq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_crate::Serialize, serde_crate::Deserialize),
+    serde(crate = "serde_crate")
+)]
 pub enum ExitError {
     /// Trying to pop from an empty stack.
     StackUnderflow,
@@ -166,6 +180,16 @@ impl ToStr for ExitError {
     }
 }
 
+impl ExitError {
+    /// Description of the error, keeping the message of `Other`.
+    pub fn message(&self) -> Cow<'static, str> {
+        match self {
+            ExitError::Other(message) => message.clone(),
+            error => Cow::Borrowed(error.clone().to_str()),
+        }
+    }
+}
+
 impl From<ExitError> for ExitReason {
     fn from(s: ExitError) -> Self {
         Self::Error(s)
@@ -173,9 +197,12 @@ impl From<ExitError> for ExitReason {
 }
 
 /// Exit fatal reason.
-#[derive(Clone, Debug, Eq, PartialEq)]
-#[cfg_attr(feature = "with-codec", derive(codec::Encode, codec::Decode))]
-#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, Eq, PartialEq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_crate::Serialize, serde_crate::Deserialize),
+    serde(crate = "serde_crate")
+)]
 pub enum ExitFatal {
     /// The operation is not supported.
     NotSupported,
@@ -199,6 +226,19 @@ impl ToStr for ExitFatal {
     }
 }
 
+impl ExitFatal {
+    /// Description of the error, keeping inner errors and messages.
+    pub fn message(&self) -> Cow<'static, str> {
+        match self {
+            ExitFatal::CallErrorAsFatal(error) => {
+                Cow::Owned(format!("CallErrorAsFatal({})", error.message()))
+            }
+            ExitFatal::Other(message) => message.clone(),
+            fatal => Cow::Borrowed(fatal.clone().to_str()),
+        }
+    }
+}
+
 impl From<ExitFatal> for ExitReason {
     fn from(s: ExitFatal) -> Self {
         Self::Fatal(s)
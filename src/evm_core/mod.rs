@@ -16,10 +16,10 @@ mod utils;
 mod valids;
 
 pub use crate::evm_core::error::{
-    Capture, ExitError, ExitFatal, ExitReason, ExitRevert, ExitSucceed, ToStr, Trap,
+    Capture, ExitError, ExitFatal, ExitReason, ExitRevert, ExitSucceed, Trap,
 };
 pub use crate::evm_core::memory::Memory;
-pub use crate::evm_core::opcode::{ExternalOpcode, Opcode};
+pub use crate::evm_core::opcode::{mnemonic, ExternalOpcode, Opcode};
 pub use crate::evm_core::stack::Stack;
 pub use crate::evm_core::valids::Valids;
 
@@ -41,6 +41,8 @@ pub struct Machine {
     code: Rc<Vec<u8>>,
     /// Program counter.
     position: Result<usize, ExitReason>,
+    /// Program counter of the opcode stepped last.
+    last_position: usize,
     /// Return value.
     return_range: Range<U256>,
     /// Code validity maps.
@@ -83,6 +85,7 @@ impl Machine {
             data,
             code,
             position: Ok(0),
+            last_position: 0,
             return_range: U256::zero()..U256::zero(),
             valids,
             memory: Memory::new(memory_limit),
@@ -95,16 +98,23 @@ impl Machine {
         self.position = Err(reason);
     }
 
-    /// Inspect the machine's next opcode and current stack.
-    #[allow(unused)]
-    pub fn inspect(&self) -> Option<(Result<Opcode, ExternalOpcode>, &Stack)> {
+    /// Inspect the machine's program counter, next opcode and current stack.
+    #[cfg_attr(not(feature = "tracing"), allow(unused))]
+    pub fn inspect(&self) -> Option<(usize, u8, &Stack)> {
         let position = match self.position {
             Ok(position) => position,
             Err(_) => return None,
         };
-        self.code
-            .get(position)
-            .map(|v| (Opcode::parse(*v), &self.stack))
+        self.code.get(position).map(|v| (position, *v, &self.stack))
+    }
+
+    /// Program counter and opcode of the last step, e.g. the one which
+    /// exited the machine with an error.
+    pub fn last_step(&self) -> (usize, Option<u8>) {
+        (
+            self.last_position,
+            self.code.get(self.last_position).cloned(),
+        )
     }
 
     /// Copy and get the return value of the machine, if any.
@@ -150,6 +160,7 @@ impl Machine {
             .position
             .as_ref()
             .map_err(|reason| Capture::Exit(reason.clone()))?;
+        self.last_position = position;
 
         match self.code.get(position).map(|v| Opcode::parse(*v)) {
             Some(Ok(opcode)) => match eval(self, opcode, position) {
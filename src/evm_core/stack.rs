@@ -32,6 +32,12 @@ impl Stack {
         self.data.len()
     }
 
+    /// Stack values, with the top of the stack last.
+    #[cfg_attr(not(feature = "tracing"), allow(unused))]
+    pub fn data(&self) -> &[H256] {
+        &self.data
+    }
+
     /// Pop a value from the stack. If the stack is already empty, returns the
     /// `StackUnderflow` error.
     pub fn pop(&mut self) -> Result<H256, ExitError> {
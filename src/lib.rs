@@ -1,3 +1,14 @@
+//! EVM for NEAR: the contract with the `contract` feature, and the
+//! interpreter, backends and tracers it is built from.
+//!
+//! # Gas
+//!
+//! EVM gas is not metered, executions are bounded by the NEAR gas of the call
+//! instead. Wherever EVM gas is expected, e.g. the `GAS` opcode, gas given to
+//! calls and traces, the backend's `gas_left` is used, which stays the same
+//! for the whole execution. Gas costs and EVM gas used are therefore always
+//! zero, and receipts report the NEAR gas burnt instead.
+
 #![cfg_attr(not(feature = "std"), no_std)]
 #![cfg_attr(not(feature = "std"), feature(core_intrinsics))]
 #![cfg_attr(not(feature = "std"), feature(alloc_error_handler))]
@@ -7,30 +18,47 @@ extern crate alloc;
 #[cfg(not(feature = "std"))]
 extern crate core;
 
+extern crate borsh_crate as borsh;
+
 pub mod backend;
+pub mod connector;
+pub mod disasm;
+#[cfg(feature = "fuzzing")]
+pub mod evm_core;
+#[cfg(not(feature = "fuzzing"))]
 mod evm_core;
-mod precompiles;
+pub mod precompiles;
 pub mod runner;
 mod runtime;
 mod stack;
+pub mod tracer;
+pub mod trie;
 pub mod types;
 
-#[cfg(feature = "contract")]
+#[cfg(any(feature = "contract", test))]
+#[cfg_attr(test, allow(dead_code))]
 mod near_backend;
-#[cfg(feature = "contract")]
+#[cfg(all(feature = "contract", not(test)))]
+mod sdk;
+#[cfg(test)]
+#[allow(dead_code)]
+#[path = "sdk_mock.rs"]
 mod sdk;
-
-use evm_core::ToStr;
 
 #[cfg(feature = "contract")]
 mod contract {
-    use borsh::BorshDeserialize;
+    use borsh::{BorshDeserialize, BorshSerialize};
 
+    use crate::backend::CachedBackend;
     use crate::near_backend::Backend;
 
     use super::*;
-    use crate::evm_core::ExitReason;
-    use crate::types::{near_account_to_evm_address, u256_to_arr, GetStorageAtArgs};
+    use crate::connector::{mint_input, FtOnTransferArgs};
+    use crate::types::{
+        near_account_to_evm_address, u256_to_arr, CleanupStorageArgs, DeployErc20TokenArgs,
+        FunctionCallArgs, GetStorageAtArgs, ImportStateArgs, StateDiffResult, TransactionStatus,
+        ViewCallArgs,
+    };
     use primitive_types::{H160, H256};
 
     // TODO: consider making a parameter, but migth cost extra.
@@ -55,31 +83,158 @@ mod contract {
         near_account_to_evm_address(&sdk::predecessor_account_id())
     }
 
-    fn process_exit_reason(reason: ExitReason, return_value: &[u8]) {
-        match reason {
-            ExitReason::Succeed(_) => sdk::return_output(return_value),
-            ExitReason::Revert(_) => sdk::panic_hex(&return_value),
-            ExitReason::Error(error) => sdk::panic_utf8(error.to_str().as_bytes()),
-            ExitReason::Fatal(error) => sdk::panic_utf8(error.to_str().as_bytes()),
+    /// Address the connector uses to deploy and mint ERC-20 tokens.
+    fn connector_address() -> H160 {
+        near_account_to_evm_address(&sdk::current_account_id())
+    }
+
+    /// Panics with the reason of failure unless the transaction succeeded.
+    fn assert_succeed(status: &TransactionStatus) {
+        match status {
+            TransactionStatus::Succeed => (),
+            TransactionStatus::Revert(message) => sdk::panic_utf8(message.as_bytes()),
+            TransactionStatus::Error(error) => sdk::panic_utf8(error.message().as_bytes()),
+        }
+    }
+
+    /// Panics until all accounts are migrated to the current layout, see
+    /// `migrate_accounts`, and while `import_state` is not finalized.
+    fn assert_state_ready() {
+        if !Backend::is_migrated() {
+            sdk::panic_utf8(b"ERR_MIGRATION_PENDING");
+        }
+        if Backend::is_import_pending() {
+            sdk::panic_utf8(b"ERR_IMPORT_PENDING");
         }
     }
 
     #[no_mangle]
     pub extern "C" fn deploy_code() {
+        assert_state_ready();
         let input = sdk::read_input();
-        let mut backend = Backend::new(CHAIN_ID, predecessor_address());
-        let (reason, return_value) = runner::Runner::deploy_code(&mut backend, &input);
-        // TODO: charge for storage.
-        process_exit_reason(reason, &return_value.0);
+        let origin = predecessor_address();
+        let mut backend = CachedBackend::new(Backend::new(CHAIN_ID, origin));
+        let storage_usage = sdk::storage_usage();
+        let used_gas = sdk::used_gas();
+        let mut receipt = runner::Runner::deploy_code(&mut backend, &input);
+        Backend::charge_storage(&origin, storage_usage);
+        receipt.gas_used = sdk::used_gas() - used_gas;
+        sdk::return_output(&receipt.try_to_vec().unwrap());
     }
 
     #[no_mangle]
     pub extern "C" fn call() {
+        assert_state_ready();
         let input = sdk::read_input();
-        let mut backend = Backend::new(CHAIN_ID, predecessor_address());
-        let (reason, return_value) = runner::Runner::call(&mut backend, &input);
-        // TODO: charge for storage.
-        process_exit_reason(reason, &return_value);
+        let origin = predecessor_address();
+        let mut backend = CachedBackend::new(Backend::new(CHAIN_ID, origin));
+        let storage_usage = sdk::storage_usage();
+        let used_gas = sdk::used_gas();
+        let mut receipt = runner::Runner::call(&mut backend, &input);
+        Backend::charge_storage(&origin, storage_usage);
+        receipt.gas_used = sdk::used_gas() - used_gas;
+        sdk::return_output(&receipt.try_to_vec().unwrap());
+    }
+
+    /// Deploys the ERC-20 contract for a NEP-141 token. Can only be called by
+    /// the EVM account itself, attaching a deposit for the storage used.
+    #[no_mangle]
+    pub extern "C" fn deploy_erc20_token() {
+        if sdk::predecessor_account_id() != sdk::current_account_id() {
+            sdk::panic_utf8(b"ERR_PRIVATE_METHOD");
+        }
+        let args = DeployErc20TokenArgs::try_from_slice(&sdk::read_input()).unwrap();
+        assert_state_ready();
+        if Backend::get_erc20_token(&args.nep141).is_some() {
+            sdk::panic_utf8(b"ERR_TOKEN_ALREADY_DEPLOYED");
+        }
+        let storage_usage = sdk::storage_usage();
+        let mut backend = CachedBackend::new(Backend::new(CHAIN_ID, connector_address()));
+        let receipt = runner::Runner::deploy_code(&mut backend, &args.code);
+        assert_succeed(&receipt.status);
+        let address = receipt.contract_address.unwrap();
+        Backend::set_erc20_token(&args.nep141, &H160(address));
+        Backend::charge_storage(&connector_address(), storage_usage);
+        sdk::return_output(&address);
+    }
+
+    /// NEP-141 receiver: mints the received tokens in the connected ERC-20
+    /// contract to the EVM address given in `msg`. The storage used is
+    /// charged to the EVM address of the sender.
+    #[no_mangle]
+    pub extern "C" fn ft_on_transfer() {
+        assert_state_ready();
+        let args = match FtOnTransferArgs::parse(&sdk::read_input()) {
+            Some(args) => args,
+            None => sdk::panic_utf8(b"ERR_INVALID_ARGS"),
+        };
+        let erc20 = match Backend::get_erc20_token(&sdk::predecessor_account_id()) {
+            Some(erc20) => erc20,
+            None => sdk::panic_utf8(b"ERR_NOT_CONNECTED_TOKEN"),
+        };
+        let input = FunctionCallArgs {
+            contract: erc20.0,
+            input: mint_input(&args.recipient, &args.amount),
+        }
+        .try_to_vec()
+        .unwrap();
+        let storage_usage = sdk::storage_usage();
+        let mut backend = CachedBackend::new(Backend::new(CHAIN_ID, connector_address()));
+        let receipt = runner::Runner::call(&mut backend, &input);
+        assert_succeed(&receipt.status);
+        Backend::charge_storage(&near_account_to_evm_address(&args.sender_id), storage_usage);
+        // All tokens are used.
+        sdk::return_output(b"\"0\"");
+    }
+
+    /// Imports accounts from borsh `ImportStateArgs`, to migrate existing
+    /// state into a fresh deployment. Can only be called by the EVM account
+    /// itself, attaching a deposit for the storage used, until a batch with
+    /// `finalize` set locks further imports. Calls and deployments fail with
+    /// `ERR_IMPORT_PENDING` in between.
+    #[no_mangle]
+    pub extern "C" fn import_state() {
+        if sdk::predecessor_account_id() != sdk::current_account_id() {
+            sdk::panic_utf8(b"ERR_PRIVATE_METHOD");
+        }
+        if Backend::is_import_finalized() {
+            sdk::panic_utf8(b"ERR_IMPORT_FINALIZED");
+        }
+        let args = ImportStateArgs::try_from_slice(&sdk::read_input()).unwrap();
+        let storage_usage = sdk::storage_usage();
+        for account in args.accounts.iter() {
+            Backend::import_account(account);
+        }
+        if args.finalize {
+            Backend::finalize_import();
+        } else {
+            Backend::start_import();
+        }
+        Backend::charge_storage(&connector_address(), storage_usage);
+    }
+
+    /// Reclaims NEAR storage of slots made unreachable by SELFDESTRUCT or
+    /// storage reset. Returns the number of removed slots.
+    #[no_mangle]
+    pub extern "C" fn cleanup_storage() {
+        let args = CleanupStorageArgs::try_from_slice(&sdk::read_input()).unwrap();
+        let storage_usage = sdk::storage_usage();
+        let removed = Backend::cleanup_storage(&H160(args.address), args.limit as usize);
+        Backend::charge_storage(&predecessor_address(), storage_usage);
+        sdk::return_output(&(removed as u64).try_to_vec().unwrap());
+    }
+
+    /// Moves accounts from the legacy storage layout to account records, up
+    /// to the limit given as borsh-serialized `u64`. Returns the number of
+    /// migrated accounts, zero once all of them are migrated. Calls and
+    /// deployments fail with `ERR_MIGRATION_PENDING` until then.
+    #[no_mangle]
+    pub extern "C" fn migrate_accounts() {
+        let limit = u64::try_from_slice(&sdk::read_input()).unwrap();
+        let storage_usage = sdk::storage_usage();
+        let migrated = Backend::migrate_accounts(limit as usize);
+        Backend::charge_storage(&predecessor_address(), storage_usage);
+        sdk::return_output(&(migrated as u64).try_to_vec().unwrap());
     }
 
     // TODO: raw_call
@@ -90,9 +245,27 @@ mod contract {
     pub extern "C" fn view() {
         let input = sdk::read_input();
         let args = crate::types::ViewCallArgs::try_from_slice(&input).unwrap();
-        let mut backend = Backend::new(CHAIN_ID, H160::from_slice(&args.sender));
-        let (reason, return_value) = runner::Runner::view(&mut backend, args);
-        process_exit_reason(reason, &return_value);
+        let mut backend =
+            CachedBackend::new(Backend::new(CHAIN_ID, H160::from_slice(&args.sender)));
+        let (status, return_value) = runner::Runner::view(&mut backend, args);
+        assert_succeed(&status);
+        sdk::return_output(&return_value);
+    }
+
+    /// Runs a view call given as borsh `ViewCallArgs`, returning borsh
+    /// `StateDiffResult` with the changes it would make to the state. Failed
+    /// calls are returned too, to be inspected.
+    #[no_mangle]
+    pub extern "C" fn view_with_state_diff() {
+        let args = ViewCallArgs::try_from_slice(&sdk::read_input()).unwrap();
+        let mut backend =
+            CachedBackend::new(Backend::new(CHAIN_ID, H160::from_slice(&args.sender)));
+        let (status, result, state_diff) = runner::Runner::view_with_state_diff(&mut backend, args);
+        sdk::return_output(
+            &StateDiffResult::new(status, result, state_diff)
+                .try_to_vec()
+                .unwrap(),
+        );
     }
 
     #[no_mangle]
@@ -116,6 +289,14 @@ mod contract {
         sdk::return_output(&u256_to_arr(&nonce))
     }
 
+    /// Logs bloom of all transactions at the NEAR block height given as
+    /// borsh-serialized `u64`.
+    #[no_mangle]
+    pub extern "C" fn get_block_bloom() {
+        let height = u64::try_from_slice(&sdk::read_input()).unwrap();
+        sdk::return_output(&Backend::get_block_bloom(height).0)
+    }
+
     #[no_mangle]
     pub extern "C" fn get_storage_at() {
         let input = sdk::read_input();
@@ -3,13 +3,27 @@ use alloc::vec::Vec;
 #[cfg(feature = "std")]
 use std::vec::Vec;
 
+use borsh::{BorshDeserialize, BorshSerialize};
 use primitive_types::{H160, H256, U256};
 
 use crate::backend::{Apply, ApplyBackend, Basic, Log};
+use crate::connector::ft_transfer_args;
+use crate::precompiles::{EXIT_FT_TO_NEAR_ADDRESS, EXIT_TO_NEAR_ADDRESS};
 use crate::sdk;
+#[cfg(feature = "json_logs")]
+use crate::types::log_to_event_json;
 use crate::types::{
-    address_to_key, bytes_to_hex, log_to_bytes, storage_to_key, u256_to_arr, KeyPrefix,
+    address_to_key, bytes_to_key, hash_to_key, keccak, storage_to_key, u256_to_arr, AccountRecord,
+    Bloom, ExitFtToNearArgs, ExitToNearArgs, ImportAccount, KeyPrefix,
 };
+#[cfg(not(feature = "json_logs"))]
+use crate::types::{bytes_to_hex, log_to_versioned_bytes};
+
+/// Gas attached to `ft_transfer` calls on NEP-141 token contracts.
+const FT_TRANSFER_GAS: u64 = 10_000_000_000_000;
+
+/// Price of a byte of NEAR storage in yoctoNEAR.
+const STORAGE_PRICE_PER_BYTE: u128 = 10_000_000_000_000_000_000;
 
 pub struct Backend {
     chain_id: U256,
@@ -24,92 +38,373 @@ impl Backend {
         }
     }
 
-    pub fn set_code(address: &H160, code: &[u8]) {
-        sdk::write_storage(&address_to_key(KeyPrefix::Code, address), code);
+    /// Account record of the address, default if it doesn't exist.
+    pub fn get_account(address: &H160) -> AccountRecord {
+        Self::read_account(address).unwrap_or_default()
     }
 
-    pub fn remove_code(address: &H160) {
-        sdk::remove_storage(&address_to_key(KeyPrefix::Code, address))
+    fn read_account(address: &H160) -> Option<AccountRecord> {
+        sdk::read_storage(&address_to_key(KeyPrefix::Account, address))
+            .map(|value| AccountRecord::try_from_slice(&value).unwrap())
     }
 
-    pub fn get_code(address: &H160) -> Vec<u8> {
-        sdk::read_storage(&address_to_key(KeyPrefix::Code, address)).unwrap_or_else(Vec::new)
-    }
-
-    pub fn set_nonce(address: &H160, nonce: &U256) {
+    fn set_account(address: &H160, account: &AccountRecord) {
         sdk::write_storage(
-            &address_to_key(KeyPrefix::Nonce, address),
-            &u256_to_arr(nonce),
+            &address_to_key(KeyPrefix::Account, address),
+            &account.try_to_vec().unwrap(),
         );
     }
 
-    pub fn remove_nonce(address: &H160) {
-        sdk::remove_storage(&address_to_key(KeyPrefix::Nonce, address))
+    /// Points the account at `code`, releasing its previous code. Code is
+    /// stored once per code hash and reference counted by the accounts using
+    /// it.
+    fn update_code(account: &mut AccountRecord, code: &[u8]) {
+        let code_hash = keccak(code);
+        if account.code_hash == code_hash.0 {
+            return;
+        }
+        Self::release_code(account);
+        if !code.is_empty() {
+            let ref_count = Self::get_code_ref_count(&code_hash);
+            if ref_count == 0 {
+                sdk::write_storage(&hash_to_key(KeyPrefix::Code, &code_hash), code);
+            }
+            Self::set_code_ref_count(&code_hash, ref_count + 1);
+        }
+        account.code_hash = code_hash.0;
+        account.code_size = code.len() as u64;
     }
 
-    pub fn get_nonce(address: &H160) -> U256 {
-        sdk::read_storage(&address_to_key(KeyPrefix::Nonce, address))
-            .map(|value| U256::from_big_endian(&value))
-            .unwrap_or_else(U256::zero)
+    fn release_code(account: &AccountRecord) {
+        if account.code_size == 0 {
+            return;
+        }
+        let code_hash = H256(account.code_hash);
+        let ref_count = Self::get_code_ref_count(&code_hash) - 1;
+        if ref_count == 0 {
+            sdk::remove_storage(&hash_to_key(KeyPrefix::Code, &code_hash));
+            sdk::remove_storage(&hash_to_key(KeyPrefix::CodeRefCount, &code_hash));
+        } else {
+            Self::set_code_ref_count(&code_hash, ref_count);
+        }
     }
 
-    pub fn set_balance(address: &H160, balance: &U256) {
-        sdk::write_storage(
-            &address_to_key(KeyPrefix::Balance, address),
-            &u256_to_arr(balance),
-        );
+    pub fn get_code(address: &H160) -> Vec<u8> {
+        Self::read_code(&Self::get_account(address))
     }
 
-    pub fn remove_balance(address: &H160) {
-        sdk::remove_storage(&address_to_key(KeyPrefix::Balance, address))
+    fn read_code(account: &AccountRecord) -> Vec<u8> {
+        if account.code_size == 0 {
+            return Vec::new();
+        }
+        sdk::read_storage(&hash_to_key(KeyPrefix::Code, &H256(account.code_hash)))
+            .unwrap_or_else(Vec::new)
     }
 
-    pub fn get_balance(address: &H160) -> U256 {
-        sdk::read_storage(&address_to_key(KeyPrefix::Balance, address))
-            .map(|value| U256::from_big_endian(&value))
-            .unwrap_or_else(U256::zero)
+    fn get_code_ref_count(code_hash: &H256) -> u64 {
+        sdk::read_storage(&hash_to_key(KeyPrefix::CodeRefCount, code_hash))
+            .map(|value| {
+                let mut bytes = [0u8; 8];
+                bytes.copy_from_slice(&value);
+                u64::from_be_bytes(bytes)
+            })
+            .unwrap_or(0)
     }
 
-    pub fn remove_storage(address: &H160, key: &H256) {
-        sdk::remove_storage(&storage_to_key(address, key));
+    fn set_code_ref_count(code_hash: &H256, ref_count: u64) {
+        sdk::write_storage(
+            &hash_to_key(KeyPrefix::CodeRefCount, code_hash),
+            &ref_count.to_be_bytes(),
+        );
+    }
+
+    pub fn get_nonce(address: &H160) -> U256 {
+        U256::from_big_endian(&Self::get_account(address).nonce)
     }
 
-    pub fn set_storage(address: &H160, key: &H256, value: &H256) {
-        sdk::write_storage(&storage_to_key(address, key), &value.0);
+    pub fn get_balance(address: &H160) -> U256 {
+        U256::from_big_endian(&Self::get_account(address).balance)
     }
 
     pub fn get_storage(address: &H160, key: &H256) -> H256 {
-        sdk::read_storage(&storage_to_key(address, key))
+        let generation = Self::get_account(address).generation;
+        sdk::read_storage(&storage_to_key(address, generation, key))
             .map(|value| H256::from_slice(&value))
             .unwrap_or_else(H256::default)
     }
 
-    pub fn is_account_empty(address: &H160) -> bool {
-        let balance = Self::get_balance(address);
-        let nonce = Self::get_nonce(address);
-        let code_len = Self::get_code(address).len();
-        balance == U256::zero() && nonce == U256::zero() && code_len == 0
+    /// Removes up to `limit` storage slots of previous generations of the
+    /// address. Returns the number of removed slots.
+    ///
+    /// There is no way to prefix delete from trie state, so resetting storage
+    /// bumps the storage generation instead, making all current slots
+    /// unreachable until they are reclaimed here.
+    pub fn cleanup_storage(address: &H160, limit: usize) -> usize {
+        let generation = Self::get_account(address).generation;
+        if generation == 0 {
+            return 0;
+        }
+        // Storage can't be modified while iterating.
+        let mut keys = Vec::new();
+        let iterator_id = sdk::storage_iter_prefix(&address_to_key(KeyPrefix::Storage, address));
+        while keys.len() < limit {
+            match sdk::storage_iter_next_key(iterator_id) {
+                Some(key) => keys.push(key),
+                None => break,
+            }
+        }
+        // Keys are iterated in order, so all stale generations come before
+        // the current one.
+        let generation = generation.to_be_bytes();
+        let iterator_id =
+            sdk::storage_iter_prefix(&address_to_key(KeyPrefix::StorageGeneration, address));
+        while keys.len() < limit {
+            match sdk::storage_iter_next_key(iterator_id) {
+                Some(key) if key[21..25] != generation => keys.push(key),
+                _ => break,
+            }
+        }
+        for key in keys.iter() {
+            sdk::remove_storage(key);
+        }
+        keys.len()
+    }
+
+    /// Removes all the account information. The storage generation is kept,
+    /// and bumped to remove all storage, as is the storage paid for. Accounts
+    /// without a record are left alone.
+    pub fn remove_account(address: &H160) {
+        let account = match Self::read_account(address) {
+            Some(account) => account,
+            None => return,
+        };
+        Self::release_code(&account);
+        Self::set_account(
+            address,
+            &AccountRecord {
+                generation: account.generation + 1,
+                storage_paid: account.storage_paid,
+                ..Default::default()
+            },
+        );
+    }
+
+    /// Charges `payer` for the NEAR storage used since `storage_usage`. The
+    /// attached deposit is used first and the EVM balance of the payer covers
+    /// the rest, panicking if it is not enough. Unused deposit is refunded to
+    /// the predecessor.
+    ///
+    /// Released storage is refunded to the EVM balance of the payer, up to
+    /// the storage it paid for, so that clearing state paid for by others
+    /// doesn't mint balance.
+    pub fn charge_storage(payer: &H160, storage_usage: u64) {
+        let mut account = Self::get_account(payer);
+        let mut new_storage_usage = sdk::storage_usage();
+        let key = address_to_key(KeyPrefix::Account, payer);
+        if new_storage_usage > storage_usage && sdk::read_storage(&key).is_none() {
+            // The record of the payer is paid for as well.
+            Self::set_account(payer, &account);
+            new_storage_usage = sdk::storage_usage();
+        }
+        let storage_price = U256::from(STORAGE_PRICE_PER_BYTE);
+        let deposit = sdk::attached_deposit();
+        let mut refund = deposit;
+        if new_storage_usage > storage_usage {
+            let bytes = new_storage_usage - storage_usage;
+            let cost = U256::from(bytes) * storage_price;
+            if cost <= U256::from(deposit) {
+                refund = deposit - cost.as_u128();
+            } else {
+                refund = 0;
+                let cost = cost - U256::from(deposit);
+                let balance = U256::from_big_endian(&account.balance);
+                if balance < cost {
+                    sdk::panic_utf8(b"ERR_NOT_ENOUGH_BALANCE_FOR_STORAGE");
+                }
+                account.balance = u256_to_arr(&(balance - cost));
+            }
+            account.storage_paid += bytes;
+            Self::set_account(payer, &account);
+        } else if new_storage_usage < storage_usage && account.storage_paid > 0 {
+            let bytes = core::cmp::min(storage_usage - new_storage_usage, account.storage_paid);
+            let balance = U256::from_big_endian(&account.balance);
+            account.balance = u256_to_arr(&(balance + U256::from(bytes) * storage_price));
+            account.storage_paid -= bytes;
+            Self::set_account(payer, &account);
+        }
+        if refund > 0 {
+            sdk::promise_transfer(&sdk::predecessor_account_id(), refund);
+        }
+    }
+
+    /// Moves up to `limit` accounts from the legacy layout, with separate
+    /// keys for nonce, balance and code by address, to account records.
+    /// Returns the number of migrated accounts, and marks the migration as
+    /// finished once none is left.
+    ///
+    /// Storage slots keep their keys, see `storage_to_key`.
+    pub fn migrate_accounts(limit: usize) -> usize {
+        // Nonce and balance were written together for every account.
+        let prefixes = [KeyPrefix::Nonce as u8, KeyPrefix::Balance as u8];
+        // Storage can't be modified while iterating.
+        let mut addresses = Vec::new();
+        for prefix in prefixes.iter() {
+            let iterator_id = sdk::storage_iter_prefix(&[*prefix]);
+            while addresses.len() < limit {
+                match sdk::storage_iter_next_key(iterator_id) {
+                    Some(key) => {
+                        let address = H160::from_slice(&key[1..]);
+                        if !addresses.contains(&address) {
+                            addresses.push(address);
+                        }
+                    }
+                    None => break,
+                }
+            }
+        }
+        for address in addresses.iter() {
+            Self::migrate_account(address);
+        }
+        if addresses.len() < limit {
+            sdk::write_storage(&bytes_to_key(KeyPrefix::Config, b"MIGRATED"), &[1]);
+        }
+        addresses.len()
     }
 
-    /// Removes all storage for given address.
-    pub fn remove_all_storage(_address: &H160) {
-        // TODO: remove storage prefix.
-        // Currently there is no way to prefix delete from trie state.
+    fn migrate_account(address: &H160) {
+        let mut account = Self::get_account(address);
+        let key = address_to_key(KeyPrefix::Nonce, address);
+        if let Some(value) = sdk::read_storage(&key) {
+            account.nonce.copy_from_slice(&value);
+            sdk::remove_storage(&key);
+        }
+        let key = address_to_key(KeyPrefix::Balance, address);
+        if let Some(value) = sdk::read_storage(&key) {
+            account.balance.copy_from_slice(&value);
+            sdk::remove_storage(&key);
+        }
+        let key = address_to_key(KeyPrefix::Code, address);
+        if let Some(value) = sdk::read_storage(&key) {
+            sdk::remove_storage(&key);
+            Self::update_code(&mut account, &value);
+        }
+        Self::set_account(address, &account);
     }
 
-    // Remove an account if its empty.
-    pub fn remove_account_if_empty(address: &H160) {
-        if Self::is_account_empty(address) {
-            Self::remove_account(address);
+    /// Whether no account is left in the legacy layout, so that the state
+    /// can be executed on. Writes storage, so only for change methods.
+    pub fn is_migrated() -> bool {
+        let key = bytes_to_key(KeyPrefix::Config, b"MIGRATED");
+        if sdk::read_storage(&key).is_some() {
+            return true;
+        }
+        // Fresh deployments have nothing to migrate, which is recorded so
+        // that later calls only read the flag.
+        let migrated = [KeyPrefix::Nonce as u8, KeyPrefix::Balance as u8]
+            .iter()
+            .all(|prefix| {
+                sdk::storage_iter_next_key(sdk::storage_iter_prefix(&[*prefix])).is_none()
+            });
+        if migrated {
+            sdk::write_storage(&key, &[1]);
+        }
+        migrated
+    }
+
+    /// Writes an account given to `import_state`, adding to the storage
+    /// already imported for it.
+    pub fn import_account(account: &ImportAccount) {
+        let address = H160(account.address);
+        let mut record = Self::get_account(&address);
+        record.nonce = account.nonce;
+        record.balance = account.balance;
+        if !account.code.is_empty() {
+            Self::update_code(&mut record, &account.code);
+        }
+        Self::set_account(&address, &record);
+        for (key, value) in account.storage.iter() {
+            if *value != [0u8; 32] {
+                sdk::write_storage(
+                    &storage_to_key(&address, record.generation, &H256(*key)),
+                    value,
+                );
+            }
         }
     }
 
-    /// Removes all the account information.
-    pub fn remove_account(address: &H160) {
-        Self::remove_nonce(address);
-        Self::remove_balance(address);
-        Self::remove_code(address);
-        Self::remove_all_storage(address);
+    fn import_status() -> Option<Vec<u8>> {
+        sdk::read_storage(&bytes_to_key(KeyPrefix::Config, b"IMPORT"))
+    }
+
+    /// Whether `import_state` has imported accounts without finalizing.
+    pub fn is_import_pending() -> bool {
+        Self::import_status().as_deref() == Some(&[0])
+    }
+
+    pub fn is_import_finalized() -> bool {
+        Self::import_status().as_deref() == Some(&[1])
+    }
+
+    pub fn start_import() {
+        sdk::write_storage(&bytes_to_key(KeyPrefix::Config, b"IMPORT"), &[0]);
+    }
+
+    pub fn finalize_import() {
+        sdk::write_storage(&bytes_to_key(KeyPrefix::Config, b"IMPORT"), &[1]);
+    }
+
+    /// Connects NEP-141 token contract `nep141` with ERC-20 contract `erc20`.
+    pub fn set_erc20_token(nep141: &[u8], erc20: &H160) {
+        sdk::write_storage(&bytes_to_key(KeyPrefix::Nep141Erc20, nep141), &erc20.0);
+        sdk::write_storage(&address_to_key(KeyPrefix::Erc20Nep141, erc20), nep141);
+    }
+
+    /// ERC-20 contract connected to NEP-141 token contract `nep141`.
+    pub fn get_erc20_token(nep141: &[u8]) -> Option<H160> {
+        sdk::read_storage(&bytes_to_key(KeyPrefix::Nep141Erc20, nep141))
+            .map(|value| H160::from_slice(&value))
+    }
+
+    /// NEP-141 token contract connected to ERC-20 contract `erc20`.
+    pub fn get_nep141_token(erc20: &H160) -> Option<Vec<u8>> {
+        sdk::read_storage(&address_to_key(KeyPrefix::Erc20Nep141, erc20))
+    }
+
+    /// Logs bloom of all transactions at NEAR block `height`.
+    pub fn get_block_bloom(height: u64) -> Bloom {
+        sdk::read_storage(&bytes_to_key(KeyPrefix::Bloom, &height.to_be_bytes()))
+            .map(|value| Bloom::try_from_slice(&value).unwrap())
+            .unwrap_or_default()
+    }
+
+    fn add_block_bloom(height: u64, bloom: &Bloom) {
+        let mut block_bloom = Backend::get_block_bloom(height);
+        block_bloom.accrue_bloom(bloom);
+        sdk::write_storage(
+            &bytes_to_key(KeyPrefix::Bloom, &height.to_be_bytes()),
+            &block_bloom.0,
+        );
+    }
+
+    /// Schedules the NEAR transfer recorded by the exit to NEAR precompile.
+    fn exit_to_near(log: &Log) {
+        let args = ExitToNearArgs::try_from_slice(&log.data).unwrap();
+        let amount = U256::from_big_endian(&args.amount).as_u128();
+        sdk::promise_transfer(&args.account_id, amount);
+    }
+
+    /// Schedules the `ft_transfer` recorded by the exit fungible token to NEAR
+    /// precompile.
+    fn exit_ft_to_near(log: &Log) {
+        let args = ExitFtToNearArgs::try_from_slice(&log.data).unwrap();
+        let amount = U256::from_big_endian(&args.amount).as_u128();
+        sdk::promise_create(
+            &args.nep141,
+            b"ft_transfer",
+            &ft_transfer_args(&args.account_id, amount),
+            1,
+            FT_TRANSFER_GAS,
+        );
     }
 }
 
@@ -156,22 +451,24 @@ impl crate::backend::Backend for Backend {
     }
 
     fn exists(&self, address: H160) -> bool {
-        Backend::get_balance(&address) > U256::zero() || Backend::get_code(&address).len() > 0
+        let account = Backend::get_account(&address);
+        account.balance != [0u8; 32] || account.code_size > 0
     }
 
     fn basic(&self, address: H160) -> Basic {
+        let account = Backend::get_account(&address);
         Basic {
-            nonce: Backend::get_nonce(&address),
-            balance: Backend::get_balance(&address),
+            nonce: U256::from_big_endian(&account.nonce),
+            balance: U256::from_big_endian(&account.balance),
         }
     }
 
     fn code_hash(&self, address: H160) -> H256 {
-        crate::types::keccak(&Backend::get_code(&address))
+        H256(Backend::get_account(&address).code_hash)
     }
 
     fn code_size(&self, address: H160) -> usize {
-        Backend::get_code(&address).len()
+        Backend::get_account(&address).code_size as usize
     }
 
     fn code(&self, address: H160) -> Vec<u8> {
@@ -199,34 +496,464 @@ impl ApplyBackend for Backend {
                     storage,
                     reset_storage,
                 } => {
-                    Backend::set_nonce(&address, &basic.nonce);
-                    Backend::set_balance(&address, &basic.balance);
+                    let stored = Backend::read_account(&address);
+                    let existed = stored.is_some();
+                    let mut account = stored.unwrap_or_default();
+                    account.nonce = u256_to_arr(&basic.nonce);
+                    account.balance = u256_to_arr(&basic.balance);
                     if let Some(code) = code {
-                        Backend::set_code(&address, &code);
+                        Backend::update_code(&mut account, &code);
                     }
-
                     if reset_storage {
-                        Backend::remove_all_storage(&address);
+                        account.generation += 1;
                     }
 
+                    if delete_empty && account.is_empty() {
+                        // Touched accounts that never had a record have
+                        // nothing to remove, so don't create one.
+                        if !existed {
+                            continue;
+                        }
+                        // Nothing but storage left to remove.
+                        account.generation += 1;
+                        Backend::set_account(&address, &account);
+                        continue;
+                    }
+                    Backend::set_account(&address, &account);
+
                     for (index, value) in storage {
+                        let key = storage_to_key(&address, account.generation, &index);
                         if value == H256::default() {
-                            Backend::remove_storage(&address, &index);
+                            sdk::remove_storage(&key);
                         } else {
-                            Backend::set_storage(&address, &index, &value);
+                            sdk::write_storage(&key, &value.0);
                         }
                     }
-
-                    if delete_empty {
-                        Backend::remove_account_if_empty(&address);
-                    }
                 }
                 Apply::Delete { address } => Backend::remove_account(&address),
             }
         }
 
-        for log in logs {
-            sdk::log_utf8(&bytes_to_hex(&log_to_bytes(log)).into_bytes());
+        let mut bloom = Bloom::default();
+        for (index, log) in logs.into_iter().enumerate() {
+            bloom.accrue_log(&log);
+            if log.address == EXIT_TO_NEAR_ADDRESS {
+                Backend::exit_to_near(&log);
+            } else if log.address == EXIT_FT_TO_NEAR_ADDRESS {
+                Backend::exit_ft_to_near(&log);
+            }
+            #[cfg(feature = "json_logs")]
+            sdk::log_utf8(log_to_event_json(&log, index as u32).as_bytes());
+            #[cfg(not(feature = "json_logs"))]
+            sdk::log_utf8(&bytes_to_hex(&log_to_versioned_bytes(log, index as u32)).into_bytes());
+        }
+        if !bloom.is_empty() {
+            Backend::add_block_bloom(sdk::block_index(), &bloom);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connector::{mint_input, FtOnTransferArgs};
+    use crate::runner::Runner;
+    use crate::sdk::Promise;
+    use crate::types::{
+        hex_to_bytes, near_account_to_evm_address, FunctionCallArgs, ImportStateArgs,
+        TransactionStatus,
+    };
+
+    impl Backend {
+        fn set_code(address: &H160, code: &[u8]) {
+            let mut account = Self::get_account(address);
+            Self::update_code(&mut account, code);
+            Self::set_account(address, &account);
+        }
+
+        fn set_balance(address: &H160, balance: &U256) {
+            let mut account = Self::get_account(address);
+            account.balance = u256_to_arr(balance);
+            Self::set_account(address, &account);
+        }
+
+        fn set_storage(address: &H160, key: &H256, value: &H256) {
+            let generation = Self::get_account(address).generation;
+            sdk::write_storage(&storage_to_key(address, generation, key), &value.0);
+        }
+    }
+
+    /// Stand-in for `EvmErc20.sol` with its `mint`, `balanceOf` and
+    /// `withdrawToNear`, keeping the balance of each address in the storage
+    /// slot of the same number and the admin in slot 0:
+    ///
+    /// ```text
+    /// CALLER PUSH1 0 SSTORE, then return the runtime code
+    /// selector := CALLDATALOAD(0) >> 224
+    /// if selector == mint: goto mint
+    /// if selector == balanceOf: goto balance_of
+    /// if selector == withdrawToNear: goto withdraw
+    /// fail: REVERT(0, 0)
+    /// mint: if SLOAD(0) != CALLER: goto fail
+    ///     SSTORE(dst, SLOAD(dst) + amt), return true
+    /// balance_of: return SLOAD(whom)
+    /// withdraw: if SLOAD(CALLER) < amt: goto fail
+    ///     SSTORE(CALLER, SLOAD(CALLER) - amt)
+    ///     MSTORE(0, amt), copy recipient to memory at 32
+    ///     if !CALL(gas, EXIT_FT_TO_NEAR, 0, 0, 32 + len, 0, 0): goto fail
+    /// ```
+    const TOKEN_CODE: &str = "3360005560a080600f6000396000f360003560e01c806340c10f1914602957806370a08231146049\
+                              5780636b351848146056575b600080fd5b600054331415602457602435600435805482019055600160\
+                              005260206000f35b6004355460005260206000f35b60243580335481811060245703335560005260\
+                              043560040180358091602001602037602001600080916000600073b4eec51c2b5879e479095dc219\
+                              ad4edf9f1d30b55af11560245700";
+
+    fn withdraw_to_near_input(recipient: &[u8], amount: u64) -> Vec<u8> {
+        let mut input = vec![0x6b, 0x35, 0x18, 0x48];
+        input.extend_from_slice(&u256_to_arr(&U256::from(0x40)));
+        input.extend_from_slice(&u256_to_arr(&U256::from(amount)));
+        input.extend_from_slice(&u256_to_arr(&U256::from(recipient.len())));
+        input.extend_from_slice(recipient);
+        input.resize(input.len() + 32 - recipient.len() % 32, 0);
+        input
+    }
+
+    fn call(origin: H160, contract: H160, input: Vec<u8>) -> TransactionStatus {
+        let mut backend = Backend::new(1, origin);
+        let args = FunctionCallArgs {
+            contract: contract.0,
+            input,
+        };
+        Runner::call(&mut backend, &args.try_to_vec().unwrap()).status
+    }
+
+    #[test]
+    fn test_exit_to_near() {
+        sdk::reset();
+        let sender = H160::from_low_u64_be(1);
+        let contract = H160::from_low_u64_be(2);
+        Backend::set_balance(&sender, &U256::from(10));
+        // CALL(gas, EXIT_TO_NEAR_ADDRESS, 1, 0, 3, 0, 0) with "bob" as input,
+        // then REVERT(0, 0).
+        let mut code = vec![0x62, b'b', b'o', b'b', 0x60, 0x00, 0x52];
+        code.extend_from_slice(&[0x60, 0x00, 0x80, 0x60, 0x03, 0x60, 0x1d, 0x60, 0x01, 0x73]);
+        code.extend_from_slice(&EXIT_TO_NEAR_ADDRESS.0);
+        code.extend_from_slice(&[0x5a, 0xf1, 0x50, 0x60, 0x00, 0x80, 0xfd]);
+        Backend::set_code(&contract, &code);
+        Backend::set_balance(&contract, &U256::from(10));
+        let mut backend = Backend::new(1, sender);
+
+        let execution =
+            Runner::execute(&mut backend, U256::zero(), true, false, None, |executor| {
+                executor.transact_call(
+                    sender,
+                    EXIT_TO_NEAR_ADDRESS,
+                    U256::from(3),
+                    b"bob.near".to_vec(),
+                )
+            });
+        assert!(execution.reason.is_succeed());
+        assert_eq!(Backend::get_balance(&sender), U256::from(7));
+        assert_eq!(Backend::get_balance(&EXIT_TO_NEAR_ADDRESS), U256::zero());
+        assert_eq!(
+            sdk::promises(),
+            vec![Promise::Transfer {
+                account_id: b"bob.near".to_vec(),
+                amount: 3,
+            }]
+        );
+
+        // Nothing is transferred when the call to the precompile is reverted.
+        let execution =
+            Runner::execute(&mut backend, U256::zero(), true, false, None, |executor| {
+                executor.transact_call(sender, contract, U256::zero(), Vec::new())
+            });
+        assert!(!execution.reason.is_succeed());
+        assert_eq!(Backend::get_balance(&contract), U256::from(10));
+        assert_eq!(sdk::promises().len(), 1);
+    }
+
+    #[test]
+    fn test_erc20_connector() {
+        sdk::reset();
+        let connector = near_account_to_evm_address(&sdk::current_account_id());
+        let alice = near_account_to_evm_address(b"alice.near");
+        let mut backend = Backend::new(1, connector);
+        let receipt = Runner::deploy_code(&mut backend, &hex_to_bytes(TOKEN_CODE).unwrap());
+        let erc20 = H160(receipt.contract_address.unwrap());
+        let balance = |address: &H160| Backend::get_storage(&erc20, &H256::from(*address));
+
+        // Mint as `ft_on_transfer` does.
+        let args = FtOnTransferArgs::parse(
+            format!(
+                r#"{{"sender_id":"alice.near","amount":"100","msg":"{:x}"}}"#,
+                alice
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        let status = call(connector, erc20, mint_input(&args.recipient, &args.amount));
+        assert_eq!(status, TransactionStatus::Succeed);
+        assert_eq!(balance(&alice), H256::from_low_u64_be(100));
+        // Only the connector can mint.
+        let status = call(alice, erc20, mint_input(&alice, &U256::one()));
+        assert!(status != TransactionStatus::Succeed);
+
+        // The exit precompile fails until the token is connected, reverting
+        // the burn.
+        let status = call(alice, erc20, withdraw_to_near_input(b"bob.near", 40));
+        assert!(status != TransactionStatus::Succeed);
+        assert_eq!(balance(&alice), H256::from_low_u64_be(100));
+        assert!(sdk::promises().is_empty());
+
+        Backend::set_erc20_token(b"token.near", &erc20);
+        let status = call(alice, erc20, withdraw_to_near_input(b"bob.near", 40));
+        assert_eq!(status, TransactionStatus::Succeed);
+        assert_eq!(balance(&alice), H256::from_low_u64_be(60));
+        assert_eq!(
+            sdk::promises(),
+            vec![Promise::FunctionCall {
+                account_id: b"token.near".to_vec(),
+                method_name: b"ft_transfer".to_vec(),
+                arguments: br#"{"receiver_id":"bob.near","amount":"40"}"#.to_vec(),
+                amount: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_storage_generations() {
+        sdk::reset();
+        let address = H160::from_low_u64_be(1);
+        let slot = H256::from_low_u64_be(2);
+        let value = H256::from_low_u64_be(3);
+        // Written with the original key layout, without a generation.
+        let mut key = vec![KeyPrefix::Storage as u8];
+        key.extend_from_slice(&address.0);
+        key.extend_from_slice(&slot.0);
+        sdk::write_storage(&key, &value.0);
+        Backend::set_balance(&address, &U256::one());
+        assert_eq!(Backend::get_storage(&address, &slot), value);
+        assert_eq!(Backend::cleanup_storage(&address, 10), 0);
+
+        Backend::remove_account(&address);
+        assert_eq!(Backend::get_storage(&address, &slot), H256::zero());
+        Backend::set_storage(&address, &slot, &value);
+        Backend::set_storage(&address, &value, &value);
+        Backend::remove_account(&address);
+        Backend::set_storage(&address, &slot, &slot);
+        assert_eq!(Backend::get_storage(&address, &slot), slot);
+        assert_eq!(Backend::cleanup_storage(&address, 2), 2);
+        assert_eq!(Backend::cleanup_storage(&address, 2), 1);
+        assert_eq!(Backend::cleanup_storage(&address, 2), 0);
+        assert_eq!(Backend::get_storage(&address, &slot), slot);
+        assert_eq!(sdk::storage_keys().len(), 2);
+    }
+
+    #[test]
+    fn test_touched_empty_account() {
+        sdk::reset();
+        let sender = H160::from_low_u64_be(1);
+        let empty = H160::from_low_u64_be(2);
+        Backend::set_balance(&sender, &U256::one());
+        let keys = sdk::storage_keys();
+
+        // A 0-value call touches the account, which is empty and deleted.
+        assert_eq!(call(sender, empty, vec![]), TransactionStatus::Succeed);
+        assert!(Backend::read_account(&empty).is_none());
+        assert_eq!(sdk::storage_keys(), keys);
+
+        Backend::remove_account(&empty);
+        assert_eq!(sdk::storage_keys(), keys);
+    }
+
+    #[test]
+    fn test_charge_storage() {
+        sdk::reset();
+        let alice = H160::from_low_u64_be(1);
+        let bob = H160::from_low_u64_be(2);
+        let contract = H160::from_low_u64_be(3);
+        let price = U256::from(STORAGE_PRICE_PER_BYTE);
+        let slot = |i| H256::from_low_u64_be(i);
+        // State that existed before storage was charged.
+        Backend::set_storage(&contract, &slot(1), &slot(1));
+        Backend::set_balance(&bob, &(price * 1000));
+
+        // Paid with the deposit, the rest of which is refunded.
+        sdk::set_attached_deposit(STORAGE_PRICE_PER_BYTE * 1000);
+        let storage_usage = sdk::storage_usage();
+        Backend::set_storage(&contract, &slot(2), &slot(2));
+        Backend::charge_storage(&alice, storage_usage);
+        let bytes = Backend::get_account(&alice).storage_paid;
+        assert_eq!(bytes, sdk::storage_usage() - storage_usage);
+        assert_eq!(
+            sdk::promises(),
+            vec![Promise::Transfer {
+                account_id: b"alice.near".to_vec(),
+                amount: STORAGE_PRICE_PER_BYTE * (1000 - bytes as u128),
+            }]
+        );
+        assert_eq!(Backend::get_balance(&alice), U256::zero());
+
+        // Paid with the EVM balance.
+        sdk::set_attached_deposit(0);
+        let storage_usage = sdk::storage_usage();
+        Backend::set_storage(&contract, &slot(3), &slot(3));
+        Backend::charge_storage(&bob, storage_usage);
+        assert_eq!(Backend::get_account(&bob).storage_paid, 53 + 32 + 40);
+        assert_eq!(Backend::get_balance(&bob), price * (1000 - 125));
+
+        // Clearing state nobody paid for is not refunded.
+        let carol = H160::from_low_u64_be(4);
+        let storage_usage = sdk::storage_usage();
+        sdk::remove_storage(&storage_to_key(&contract, 0, &slot(1)));
+        Backend::charge_storage(&carol, storage_usage);
+        assert_eq!(Backend::get_account(&carol), AccountRecord::default());
+
+        // Refunds are limited to the storage paid for.
+        let storage_usage = sdk::storage_usage();
+        sdk::remove_storage(&storage_to_key(&contract, 0, &slot(2)));
+        sdk::remove_storage(&storage_to_key(&contract, 0, &slot(3)));
+        Backend::charge_storage(&bob, storage_usage);
+        assert_eq!(Backend::get_account(&bob).storage_paid, 0);
+        assert_eq!(Backend::get_balance(&bob), price * 1000);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_ENOUGH_BALANCE_FOR_STORAGE")]
+    fn test_charge_storage_balance() {
+        sdk::reset();
+        let storage_usage = sdk::storage_usage();
+        Backend::set_storage(&H160::zero(), &H256::zero(), &H256::from_low_u64_be(1));
+        Backend::charge_storage(&H160::from_low_u64_be(1), storage_usage);
+    }
+
+    #[test]
+    fn test_code_dedup() {
+        sdk::reset();
+        let first = H160::from_low_u64_be(1);
+        let second = H160::from_low_u64_be(2);
+        let code = vec![0x60, 0x00, 0x00];
+        let code_hash = keccak(&code);
+        let code_keys = || {
+            sdk::storage_keys()
+                .into_iter()
+                .filter(|key| key[0] == KeyPrefix::Code as u8)
+                .count()
+        };
+        Backend::set_code(&first, &code);
+        Backend::set_code(&second, &code);
+        assert_eq!(code_keys(), 1);
+        assert_eq!(Backend::get_code_ref_count(&code_hash), 2);
+        assert_eq!(Backend::get_code(&second), code);
+
+        // The code size is kept in the account record.
+        sdk::remove_storage(&hash_to_key(KeyPrefix::Code, &code_hash));
+        let backend = Backend::new(1, first);
+        assert_eq!(crate::backend::Backend::code_size(&backend, second), 3);
+        sdk::write_storage(&hash_to_key(KeyPrefix::Code, &code_hash), &code);
+
+        Backend::remove_account(&first);
+        assert_eq!(code_keys(), 1);
+        assert_eq!(Backend::get_code_ref_count(&code_hash), 1);
+        Backend::set_code(&second, &[]);
+        assert_eq!(code_keys(), 0);
+        assert_eq!(Backend::get_code_ref_count(&code_hash), 0);
+    }
+
+    #[test]
+    fn test_migrate_accounts() {
+        sdk::reset();
+        // Accounts in the original layout, one of them with code and storage.
+        let addresses: Vec<_> = (1..4).map(H160::from_low_u64_be).collect();
+        let code = vec![0x60, 0x00, 0x00];
+        for (i, address) in addresses.iter().enumerate() {
+            let nonce = U256::from(i + 1);
+            let balance = U256::from(100 * (i + 1));
+            sdk::write_storage(
+                &address_to_key(KeyPrefix::Nonce, address),
+                &u256_to_arr(&nonce),
+            );
+            sdk::write_storage(
+                &address_to_key(KeyPrefix::Balance, address),
+                &u256_to_arr(&balance),
+            );
+        }
+        sdk::write_storage(&address_to_key(KeyPrefix::Code, &addresses[0]), &code);
+        Backend::set_storage(&addresses[0], &H256::repeat_byte(1), &H256::repeat_byte(2));
+        assert!(!Backend::is_migrated());
+
+        assert_eq!(Backend::migrate_accounts(2), 2);
+        assert!(!Backend::is_migrated());
+        assert_eq!(Backend::migrate_accounts(2), 1);
+        assert!(Backend::is_migrated());
+        assert_eq!(Backend::migrate_accounts(2), 0);
+
+        assert!(sdk::storage_keys().iter().all(|key| {
+            key[0] != KeyPrefix::Nonce as u8
+                && key[0] != KeyPrefix::Balance as u8
+                && !(key[0] == KeyPrefix::Code as u8 && key.len() == 21)
+        }));
+        for (i, address) in addresses.iter().enumerate() {
+            assert_eq!(Backend::get_nonce(address), U256::from(i + 1));
+            assert_eq!(Backend::get_balance(address), U256::from(100 * (i + 1)));
+        }
+        assert_eq!(Backend::get_code(&addresses[0]), code);
+        assert_eq!(Backend::get_code_ref_count(&keccak(&code)), 1);
+        assert_eq!(
+            Backend::get_storage(&addresses[0], &H256::repeat_byte(1)),
+            H256::repeat_byte(2)
+        );
+    }
+
+    #[test]
+    fn test_fresh_state_migrated() {
+        sdk::reset();
+        assert!(Backend::is_migrated());
+        // Recorded, so that the legacy layout isn't looked for again.
+        let key = address_to_key(KeyPrefix::Nonce, &H160::repeat_byte(1));
+        sdk::write_storage(&key, &[0; 32]);
+        assert!(Backend::is_migrated());
+    }
+
+    #[test]
+    fn test_import_account() {
+        sdk::reset();
+        let address = H160::repeat_byte(1);
+        let code = vec![0x60, 0x00, 0x00];
+        let account = ImportAccount {
+            address: address.0,
+            nonce: u256_to_arr(&1.into()),
+            balance: u256_to_arr(&2.into()),
+            code: code.clone(),
+            storage: (1..4u8).map(|i| ([i; 32], [i; 32])).collect(),
+        };
+        let batches = ImportStateArgs::batches(vec![account], 215, true);
+        assert_eq!(batches.len(), 2);
+        assert!(!Backend::is_import_pending());
+        for batch in batches.iter() {
+            for account in batch.accounts.iter() {
+                Backend::import_account(account);
+            }
+            if batch.finalize {
+                Backend::finalize_import();
+            } else {
+                Backend::start_import();
+                assert!(Backend::is_import_pending());
+            }
+        }
+        assert!(!Backend::is_import_pending());
+        assert!(Backend::is_import_finalized());
+
+        assert_eq!(Backend::get_nonce(&address), U256::from(1));
+        assert_eq!(Backend::get_balance(&address), U256::from(2));
+        assert_eq!(Backend::get_code(&address), code);
+        assert_eq!(Backend::get_code_ref_count(&keccak(&code)), 1);
+        for i in 1..4u8 {
+            assert_eq!(
+                Backend::get_storage(&address, &H256::repeat_byte(i)),
+                H256::repeat_byte(i)
+            );
         }
     }
 }
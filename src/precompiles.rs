@@ -1,16 +1,202 @@
+use borsh::BorshSerialize;
+use primitive_types::{H160, U256};
+
+use crate::backend::Log;
 use crate::runtime::{Context, ExitError, ExitSucceed};
-use primitive_types::H160;
+use crate::types::{is_valid_account_id, u256_to_arr, ExitFtToNearArgs, ExitToNearArgs};
 
 #[cfg(feature = "std")]
-use std::vec::Vec;
+use std::{borrow::Cow, vec, vec::Vec};
 
 #[cfg(not(feature = "std"))]
-use alloc::vec::Vec;
+use alloc::{borrow::Cow, vec, vec::Vec};
+
+/// Address of the exit to NEAR precompile: `keccak("exitToNear")[12..]`.
+pub const EXIT_TO_NEAR_ADDRESS: H160 = H160([
+    0xe9, 0x21, 0x7b, 0xc7, 0x0b, 0x7e, 0xd1, 0xf5, 0x98, 0xdd, 0xd3, 0x19, 0x9e, 0x80, 0xb0, 0x93,
+    0xfa, 0x71, 0x12, 0x4f,
+]);
+
+/// Address of the exit fungible token to NEAR precompile:
+/// `keccak("exitFtToNear")[12..]`.
+pub const EXIT_FT_TO_NEAR_ADDRESS: H160 = H160([
+    0xb4, 0xee, 0xc5, 0x1c, 0x2b, 0x58, 0x79, 0xe4, 0x79, 0x09, 0x5d, 0xc2, 0x19, 0xad, 0x4e, 0xdf,
+    0x9f, 0x1d, 0x30, 0xb5,
+]);
+
+/// Successful output of a precompile.
+pub struct PrecompileOutput {
+    /// Exit status.
+    pub exit_status: ExitSucceed,
+    /// Return data.
+    pub output: Vec<u8>,
+    /// Logs emitted by the precompile.
+    pub logs: Vec<Log>,
+    /// Amount of wei to remove from the precompile address after execution.
+    pub burn: U256,
+}
+
+impl PrecompileOutput {
+    pub fn new(exit_status: ExitSucceed, output: Vec<u8>) -> Self {
+        Self {
+            exit_status,
+            output,
+            logs: Vec::new(),
+            burn: U256::zero(),
+        }
+    }
+}
+
+pub type PrecompileResult = Result<PrecompileOutput, ExitError>;
+
+pub fn precompiles(address: H160, input: &[u8], context: &Context) -> Option<PrecompileResult> {
+    if address == EXIT_TO_NEAR_ADDRESS {
+        return Some(exit_to_near(input, context));
+    }
+    if address == EXIT_FT_TO_NEAR_ADDRESS {
+        return Some(exit_ft_to_near(input, context));
+    }
+    None
+}
+
+/// Burns the attached value and records a transfer of the same amount of
+/// yoctoNEAR to the NEAR account given as input.
+///
+/// The transfer is emitted as a log from `EXIT_TO_NEAR_ADDRESS` with
+/// borsh-serialized `ExitToNearArgs` as data. The backend turns it into a
+/// NEAR promise when the state is committed, so reverted calls never
+/// transfer anything.
+fn exit_to_near(input: &[u8], context: &Context) -> PrecompileResult {
+    // Must be called directly, otherwise value would be burned from the caller.
+    if context.address != EXIT_TO_NEAR_ADDRESS {
+        return Err(ExitError::Other(Cow::Borrowed("ERR_INVALID_CALL_SCHEME")));
+    }
+    if context.apparent_value.is_zero() {
+        return Err(ExitError::Other(Cow::Borrowed("ERR_ZERO_AMOUNT")));
+    }
+    if context.apparent_value > U256::from(u128::max_value()) {
+        return Err(ExitError::Other(Cow::Borrowed("ERR_AMOUNT_OVERFLOW")));
+    }
+    if !is_valid_account_id(input) {
+        return Err(ExitError::Other(Cow::Borrowed("ERR_INVALID_ACCOUNT_ID")));
+    }
 
-pub fn precompiles(
-    _address: H160,
-    _input: &[u8],
-    _context: &Context,
-) -> Option<Result<(ExitSucceed, Vec<u8>), ExitError>> {
+    let args = ExitToNearArgs {
+        sender: context.caller.0,
+        account_id: input.to_vec(),
+        amount: u256_to_arr(&context.apparent_value),
+    };
+    let mut output = PrecompileOutput::new(ExitSucceed::Returned, Vec::new());
+    output.logs = vec![Log {
+        address: EXIT_TO_NEAR_ADDRESS,
+        topics: Vec::new(),
+        data: args.try_to_vec().unwrap(),
+    }];
+    output.burn = context.apparent_value;
+    Ok(output)
+}
+
+/// Records an `ft_transfer` of the NEP-141 token bridged by the calling ERC-20
+/// contract. The ERC-20 contract must burn the amount before calling it.
+///
+/// Input is the 32 bytes big-endian amount followed by the NEAR account id of
+/// the receiver. The caller must be an ERC-20 contract connected to a NEP-141
+/// token. As with `exit_to_near`, the transfer is emitted as a log and
+/// scheduled by the backend on commit.
+fn exit_ft_to_near(input: &[u8], context: &Context) -> PrecompileResult {
+    if context.address != EXIT_FT_TO_NEAR_ADDRESS {
+        return Err(ExitError::Other(Cow::Borrowed("ERR_INVALID_CALL_SCHEME")));
+    }
+    if !context.apparent_value.is_zero() {
+        return Err(ExitError::Other(Cow::Borrowed("ERR_NONZERO_VALUE")));
+    }
+    if input.len() < 32 || !is_valid_account_id(&input[32..]) {
+        return Err(ExitError::Other(Cow::Borrowed("ERR_INVALID_INPUT")));
+    }
+
+    let mut amount = [0u8; 32];
+    amount.copy_from_slice(&input[..32]);
+    if U256::from_big_endian(&amount) > U256::from(u128::max_value()) {
+        return Err(ExitError::Other(Cow::Borrowed("ERR_AMOUNT_OVERFLOW")));
+    }
+    let nep141 = match nep141_token(&context.caller) {
+        Some(nep141) => nep141,
+        None => return Err(ExitError::Other(Cow::Borrowed("ERR_NOT_CONNECTED_TOKEN"))),
+    };
+    let args = ExitFtToNearArgs {
+        erc20: context.caller.0,
+        nep141,
+        account_id: input[32..].to_vec(),
+        amount,
+    };
+    let mut output = PrecompileOutput::new(ExitSucceed::Returned, Vec::new());
+    output.logs = vec![Log {
+        address: EXIT_FT_TO_NEAR_ADDRESS,
+        topics: Vec::new(),
+        data: args.try_to_vec().unwrap(),
+    }];
+    Ok(output)
+}
+
+/// NEP-141 token connected to ERC-20 contract `erc20`, from the state of the
+/// NEAR connector.
+#[cfg(any(feature = "contract", test))]
+fn nep141_token(erc20: &H160) -> Option<Vec<u8>> {
+    crate::near_backend::Backend::get_nep141_token(erc20)
+}
+
+/// There is no connector outside of the NEAR contract.
+#[cfg(not(any(feature = "contract", test)))]
+fn nep141_token(_erc20: &H160) -> Option<Vec<u8>> {
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{Backend, MemoryBackend, MemoryVicinity};
+    use crate::runner::Runner;
+    use crate::runtime::ExitReason;
+    use borsh::BorshDeserialize;
+    use std::collections::BTreeMap;
+
+    fn exit_to_near_call(value: U256, account_id: &[u8]) -> (ExitReason, Vec<Log>, U256) {
+        let sender = H160::from_low_u64_be(1);
+        let vicinity = MemoryVicinity::default();
+        let mut backend = MemoryBackend::new(&vicinity, BTreeMap::new());
+        let execution =
+            Runner::execute(&mut backend, U256::zero(), true, false, None, |executor| {
+                executor.deposit(sender, U256::from(10));
+                executor.transact_call(sender, EXIT_TO_NEAR_ADDRESS, value, account_id.to_vec())
+            });
+        let burned = backend.basic(EXIT_TO_NEAR_ADDRESS).balance;
+        (execution.reason, execution.logs, burned)
+    }
+
+    #[test]
+    fn test_exit_to_near() {
+        let (reason, logs, balance) = exit_to_near_call(U256::from(3), b"bob.near");
+        assert_eq!(reason, ExitReason::Succeed(ExitSucceed::Returned));
+        assert_eq!(balance, U256::zero());
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].address, EXIT_TO_NEAR_ADDRESS);
+        let args = ExitToNearArgs::try_from_slice(&logs[0].data).unwrap();
+        assert_eq!(args.sender, H160::from_low_u64_be(1).0);
+        assert_eq!(args.account_id, b"bob.near".to_vec());
+        assert_eq!(args.amount, u256_to_arr(&U256::from(3)));
+
+        for (value, account_id, error) in [
+            (U256::zero(), &b"bob.near"[..], "ERR_ZERO_AMOUNT"),
+            (U256::one(), &b"Bob"[..], "ERR_INVALID_ACCOUNT_ID"),
+        ]
+        .iter()
+        {
+            let (reason, logs, _) = exit_to_near_call(*value, account_id);
+            assert_eq!(
+                reason,
+                ExitReason::Error(ExitError::Other(Cow::Borrowed(*error)))
+            );
+            assert!(logs.is_empty());
+        }
+    }
+}
@@ -1,78 +1,262 @@
 #[cfg(not(feature = "std"))]
-use alloc::vec::Vec;
+use alloc::{collections::BTreeMap, vec::Vec};
 #[cfg(feature = "std")]
-use std::vec::Vec;
+use std::{collections::BTreeMap, vec::Vec};
 
 use borsh::BorshDeserialize;
-use primitive_types::{H160, U256};
+use primitive_types::{H160, H256, U256};
 
-use crate::backend::{ApplyBackend, Backend};
+use crate::backend::{Apply, ApplyBackend, Backend, Log};
 use crate::precompiles::precompiles;
-use crate::runtime::{Config, CreateScheme, ExitReason};
-use crate::stack::StackExecutor;
-use crate::types::{FunctionCallArgs, ViewCallArgs};
+use crate::runtime::ExitReason;
+pub use crate::runtime::{Config, CreateScheme};
+pub use crate::stack::{AccountDiff, Change, ErrorLocation, StackExecutor, StateDiff};
+use crate::tracer::Tracer;
+use crate::types::{FunctionCallArgs, TransactionReceipt, TransactionStatus, ViewCallArgs};
 
 pub struct Runner {}
 
+/// Outcome of `Runner::execute`.
+pub struct Execution<R> {
+    pub reason: ExitReason,
+    /// Result of the function run on the executor.
+    pub result: R,
+    pub logs: Vec<Log>,
+    /// Changes made to the state, if requested.
+    pub state_diff: Option<StateDiff>,
+    /// Where the outermost frame exited with an error, if it did.
+    pub error_location: Option<ErrorLocation>,
+}
+
+impl<R> Execution<R> {
+    /// The execution with `f` applied to its result.
+    pub fn map_result<T, F: FnOnce(R) -> T>(self, f: F) -> Execution<T> {
+        Execution {
+            reason: self.reason,
+            result: f(self.result),
+            logs: self.logs,
+            state_diff: self.state_diff,
+            error_location: self.error_location,
+        }
+    }
+}
+
+impl Execution<Vec<u8>> {
+    pub fn status(&self) -> TransactionStatus {
+        TransactionStatus::new(
+            self.reason.clone(),
+            &self.result,
+            self.error_location.as_ref(),
+        )
+    }
+
+    fn into_receipt(self, contract_address: Option<H160>) -> TransactionReceipt {
+        TransactionReceipt::new(self.status(), self.result, contract_address, self.logs)
+    }
+}
+
 impl Runner {
+    /// Runs `f` on a new executor, committing the resulting state if
+    /// `should_commit`, computing the changes made to it if
+    /// `with_state_diff` and reporting the execution to `tracer`, if given.
     pub fn execute<B, F, R>(
         backend: &mut B,
         _value: U256,
         should_commit: bool,
+        with_state_diff: bool,
+        tracer: Option<&mut dyn Tracer>,
         f: F,
-    ) -> (ExitReason, R)
+    ) -> Execution<R>
     where
         B: ApplyBackend + Backend,
         F: FnOnce(&mut StackExecutor<B>) -> (ExitReason, R),
     {
-        let config = Config::istanbul();
-        #[cfg(feature = "external_machine")]
+        Self::execute_with_config(
+            backend,
+            &Config::istanbul(),
+            should_commit,
+            with_state_diff,
+            tracer,
+            f,
+        )
+    }
+
+    /// Like `execute`, with the rules of another hard fork than Istanbul.
+    ///
+    /// Tracers are only reported to with the `tracing` feature, and passing
+    /// one without it panics.
+    pub fn execute_with_config<B, F, R>(
+        backend: &mut B,
+        config: &Config,
+        should_commit: bool,
+        with_state_diff: bool,
+        tracer: Option<&mut dyn Tracer>,
+        f: F,
+    ) -> Execution<R>
+    where
+        B: ApplyBackend + Backend,
+        F: FnOnce(&mut StackExecutor<B>) -> (ExitReason, R),
+    {
+        #[cfg(feature = "external_evm_machine")]
         let machine = crate::runtime::evm_machine::SdkMachine {};
-        #[cfg(not(feature = "external_machine"))]
+        #[cfg(not(feature = "external_evm_machine"))]
         let machine = crate::runtime::evm_machine::EmbeddedMachine::new();
         let mut executor =
-            StackExecutor::new_with_precompile(backend, &machine, &config, precompiles);
-        let (reason, return_value) = f(&mut executor);
-        let (values, logs) = executor.deconstruct();
+            StackExecutor::new_with_precompile(backend, &machine, config, precompiles);
+        #[cfg(feature = "tracing")]
+        if let Some(tracer) = tracer {
+            executor.set_tracer(tracer);
+        }
+        #[cfg(not(feature = "tracing"))]
+        assert!(tracer.is_none(), "tracing feature is disabled");
+        let (reason, result) = f(&mut executor);
+        let (values, execution) = Self::finish(executor, reason, result, with_state_diff);
         if should_commit {
-            backend.apply(values, logs, true);
+            backend.apply(values, execution.logs.clone(), true);
         }
-        (reason, return_value)
+        execution
+    }
+
+    /// Changes of a finished executor, with the outcome of its execution.
+    fn finish<B: Backend, R>(
+        executor: StackExecutor<B>,
+        reason: ExitReason,
+        result: R,
+        with_state_diff: bool,
+    ) -> (Vec<Apply<BTreeMap<H256, H256>>>, Execution<R>) {
+        let state_diff = if with_state_diff {
+            executor.state_diff()
+        } else {
+            None
+        };
+        let error_location = executor.error_location().cloned();
+        let (values, logs) = executor.deconstruct();
+        let execution = Execution {
+            reason,
+            result,
+            logs,
+            state_diff,
+            error_location,
+        };
+        (values, execution)
     }
 
-    pub fn deploy_code<B>(backend: &mut B, input: &[u8]) -> (ExitReason, H160)
+    pub fn deploy_code<B>(backend: &mut B, input: &[u8]) -> TransactionReceipt
     where
         B: ApplyBackend + Backend,
     {
         let origin = backend.origin();
         let value = U256::zero();
-        Self::execute(backend, value, true, |executor| {
+        let execution = Self::execute(backend, value, true, false, None, |executor| {
             let address = executor.create_address(CreateScheme::Legacy { caller: origin });
-            (
-                executor.transact_create(origin, value, Vec::from(input)),
-                address,
-            )
-        })
+            let (reason, result) = executor.transact_create(origin, value, Vec::from(input));
+            (reason, (address, result))
+        });
+        let address = Some(execution.result.0).filter(|_| execution.reason.is_succeed());
+        execution
+            .map_result(|(_, result)| result)
+            .into_receipt(address)
+    }
+
+    pub fn call<B>(backend: &mut B, input: &[u8]) -> TransactionReceipt
+    where
+        B: ApplyBackend + Backend,
+    {
+        Self::call_inner(backend, input, None)
+    }
+
+    /// Runs a call from the backend origin, reporting it to `tracer`.
+    #[cfg(feature = "tracing")]
+    pub fn call_with_tracer<B>(
+        backend: &mut B,
+        input: &[u8],
+        tracer: &mut dyn Tracer,
+    ) -> TransactionReceipt
+    where
+        B: ApplyBackend + Backend,
+    {
+        Self::call_inner(backend, input, Some(tracer))
     }
 
-    pub fn call<B>(backend: &mut B, input: &[u8]) -> (ExitReason, Vec<u8>)
+    fn call_inner<B>(
+        backend: &mut B,
+        input: &[u8],
+        tracer: Option<&mut dyn Tracer>,
+    ) -> TransactionReceipt
     where
         B: ApplyBackend + Backend,
     {
         let args = FunctionCallArgs::try_from_slice(&input).unwrap();
         let origin = backend.origin();
         let value = U256::zero();
-        Self::execute(backend, value, true, |executor| {
+        Self::execute(backend, value, true, false, tracer, |executor| {
             executor.transact_call(origin, H160(args.contract), value, args.input)
         })
+        .into_receipt(None)
+    }
+
+    /// Runs a view call, reporting it to `tracer`.
+    #[cfg(feature = "tracing")]
+    pub fn view_with_tracer<B>(
+        backend: &mut B,
+        args: ViewCallArgs,
+        tracer: &mut dyn Tracer,
+    ) -> (TransactionStatus, Vec<u8>)
+    where
+        B: ApplyBackend + Backend,
+    {
+        let execution = Self::view_inner(backend, args, false, Some(tracer));
+        (execution.status(), execution.result)
+    }
+
+    pub fn view<B>(backend: &mut B, args: ViewCallArgs) -> (TransactionStatus, Vec<u8>)
+    where
+        B: ApplyBackend + Backend,
+    {
+        let execution = Self::view_inner(backend, args, false, None);
+        (execution.status(), execution.result)
     }
 
-    pub fn view<B>(backend: &mut B, args: ViewCallArgs) -> (ExitReason, Vec<u8>)
+    /// Runs a view call, returning the changes it would make to the state.
+    pub fn view_with_state_diff<B>(
+        backend: &mut B,
+        args: ViewCallArgs,
+    ) -> (TransactionStatus, Vec<u8>, StateDiff)
+    where
+        B: ApplyBackend + Backend,
+    {
+        let execution = Self::view_inner(backend, args, true, None);
+        let status = execution.status();
+        (status, execution.result, execution.state_diff.unwrap())
+    }
+
+    /// Runs a call from the backend origin without committing it, returning
+    /// the receipt and the changes it would make to the state.
+    pub fn dry_run_call<B>(backend: &mut B, input: &[u8]) -> (TransactionReceipt, StateDiff)
+    where
+        B: ApplyBackend + Backend,
+    {
+        let args = FunctionCallArgs::try_from_slice(&input).unwrap();
+        let origin = backend.origin();
+        let value = U256::zero();
+        let mut execution = Self::execute(backend, value, false, true, None, |executor| {
+            executor.transact_call(origin, H160(args.contract), value, args.input)
+        });
+        let state_diff = execution.state_diff.take().unwrap();
+        (execution.into_receipt(None), state_diff)
+    }
+
+    fn view_inner<B>(
+        backend: &mut B,
+        args: ViewCallArgs,
+        with_state_diff: bool,
+        tracer: Option<&mut dyn Tracer>,
+    ) -> Execution<Vec<u8>>
     where
         B: ApplyBackend + Backend,
     {
         let value = U256::from_big_endian(&args.amount);
-        Self::execute(backend, value, false, |executor| {
+        Self::execute(backend, value, false, with_state_diff, tracer, |executor| {
             executor.transact_call(
                 H160::from_slice(&args.sender),
                 H160::from_slice(&args.address),
@@ -180,9 +180,12 @@ pub fn gaslimit<H: Handler>(runtime: &mut Runtime, handler: &H) -> Control<H> {
     Control::Continue
 }
 
-pub fn sload<H: Handler>(runtime: &mut Runtime, handler: &H) -> Control<H> {
+pub fn sload<H: Handler>(runtime: &mut Runtime, handler: &mut H) -> Control<H> {
     pop!(runtime, index);
-    push!(runtime, handler.storage(runtime.context.address, index));
+    let value = handler.storage(runtime.context.address, index);
+    #[cfg(feature = "tracing")]
+    handler.trace_sload(runtime.context.address, index, value);
+    push!(runtime, value);
 
     Control::Continue
 }
@@ -190,7 +193,11 @@ pub fn sload<H: Handler>(runtime: &mut Runtime, handler: &H) -> Control<H> {
 pub fn sstore<H: Handler>(runtime: &mut Runtime, handler: &mut H) -> Control<H> {
     pop!(runtime, index, value);
     match handler.set_storage(runtime.context.address, index, value) {
-        Ok(()) => Control::Continue,
+        Ok(()) => {
+            #[cfg(feature = "tracing")]
+            handler.trace_sstore(runtime.context.address, index, value);
+            Control::Continue
+        }
         Err(e) => Control::Exit(e.into()),
     }
 }
@@ -372,14 +379,7 @@ pub fn call<'config, H: Handler>(
         None
     };
 
-    match handler.call(
-        to.into(),
-        transfer,
-        input,
-        gas,
-        scheme == CallScheme::StaticCall,
-        context,
-    ) {
+    match handler.call(to.into(), transfer, input, gas, scheme, context) {
         Capture::Exit((reason, return_data)) => {
             runtime.return_data_buffer = return_data;
             let target_len = min(out_len, U256::from(runtime.return_data_buffer.len()));
@@ -29,6 +29,16 @@ pub trait Machine {
     ) -> Result<(), ExitFatal>;
     fn memory_get(&self, offset: usize, size: usize) -> Vec<u8>;
     fn memory_resize(&self, offset: U256, len: U256) -> Result<(), ExitError>;
+    /// Program counter and opcode of the last step.
+    fn last_step(&self) -> (usize, Option<u8>);
+    /// Program counter and opcode about to be executed, `None` once exited.
+    #[cfg(feature = "tracing")]
+    fn position(&self) -> Option<(usize, u8)>;
+    /// Copy of the stack, with the top of the stack last.
+    #[cfg(feature = "tracing")]
+    fn stack_data(&self) -> Vec<H256>;
+    #[cfg(feature = "tracing")]
+    fn memory_size(&self) -> usize;
 }
 
 #[cfg(feature = "external_evm_machine")]
@@ -70,10 +80,25 @@ mod sdk {
         fn memory_resize(&self, offset: U256, len: U256) -> Result<(), ExitError> {
             Ok(())
         }
+        fn last_step(&self) -> (usize, Option<u8>) {
+            (0, None)
+        }
+        #[cfg(feature = "tracing")]
+        fn position(&self) -> Option<(usize, u8)> {
+            None
+        }
+        #[cfg(feature = "tracing")]
+        fn stack_data(&self) -> Vec<H256> {
+            vec![]
+        }
+        #[cfg(feature = "tracing")]
+        fn memory_size(&self) -> usize {
+            0
+        }
     }
 }
 
-#[cfg(not(feature = "external_machine"))]
+#[cfg(not(feature = "external_evm_machine"))]
 mod embedded {
     use super::*;
 
@@ -177,5 +202,37 @@ mod embedded {
                 None => panic!(),
             }
         }
+
+        #[inline]
+        fn last_step(&self) -> (usize, Option<u8>) {
+            match self.machines.borrow().last() {
+                Some(x) => x.last_step(),
+                None => panic!(),
+            }
+        }
+
+        #[cfg(feature = "tracing")]
+        fn position(&self) -> Option<(usize, u8)> {
+            match self.machines.borrow().last() {
+                Some(x) => x.inspect().map(|(position, opcode, _)| (position, opcode)),
+                None => panic!(),
+            }
+        }
+
+        #[cfg(feature = "tracing")]
+        fn stack_data(&self) -> Vec<H256> {
+            match self.machines.borrow().last() {
+                Some(x) => x.stack().data().to_vec(),
+                None => panic!(),
+            }
+        }
+
+        #[cfg(feature = "tracing")]
+        fn memory_size(&self) -> usize {
+            match self.machines.borrow().last() {
+                Some(x) => x.memory().len(),
+                None => panic!(),
+            }
+        }
     }
 }
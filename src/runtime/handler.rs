@@ -1,5 +1,6 @@
 use crate::runtime::{
-    Capture, Context, CreateScheme, ExitError, ExitReason, ExternalOpcode, Opcode, Stack,
+    CallScheme, Capture, Context, CreateScheme, ExitError, ExitReason, ExternalOpcode, Opcode,
+    Stack,
 };
 #[cfg(feature = "std")]
 use std::vec::Vec;
@@ -94,7 +95,7 @@ pub trait Handler {
         transfer: Option<Transfer>,
         input: Vec<u8>,
         target_gas: Option<usize>,
-        is_static: bool,
+        scheme: CallScheme,
         context: Context,
     ) -> Capture<(ExitReason, Vec<u8>), Self::CallInterrupt>;
     /// Feed in call feedback.
@@ -109,6 +110,15 @@ pub trait Handler {
         opcode: Result<Opcode, ExternalOpcode>,
         stack: &Stack,
     ) -> Result<(), ExitError>;
+    /// Observe the opcode at `pc`, before and after it is executed.
+    #[cfg(feature = "tracing")]
+    fn trace_step(&mut self, _pc: usize, _opcode: u8, _after: bool) {}
+    /// Observe a storage value read by `SLOAD`.
+    #[cfg(feature = "tracing")]
+    fn trace_sload(&mut self, _address: H160, _index: H256, _value: H256) {}
+    /// Observe a storage value written by `SSTORE`.
+    #[cfg(feature = "tracing")]
+    fn trace_sstore(&mut self, _address: H160, _index: H256, _value: H256) {}
     /// Handle other unknown external opcodes.
     fn other(&mut self, _opcode: u8) -> Result<(), ExitError> {
         Err(ExitError::OutOfGas)
@@ -34,15 +34,24 @@ macro_rules! step {
 		}
 
 
+		let position = $self.trace_position();
+		$self.trace_step($handler, position, false);
+
 		match $self.machine.step() {
-			Ok(()) => $($ok)?(()),
+			Ok(()) => {
+				$self.trace_step($handler, position, true);
+				$($ok)?(())
+			},
 			Err(Capture::Exit(e)) => {
 				$self.status = Err(e.clone());
+				$self.trace_step($handler, position, true);
 				#[allow(unused_parens)]
 				$return $($err)*(Capture::Exit(e))
 			},
 			Err(Capture::Trap(opcode)) => {
-				match eval::eval($self, opcode, $handler) {
+				let control = eval::eval($self, opcode, $handler);
+				$self.trace_step($handler, position, true);
+				match control {
 					eval::Control::Continue => $($ok)?(()),
 					eval::Control::CallInterrupt(interrupt) => {
 						let resolve = ResolveCall::new($self);
@@ -96,6 +105,17 @@ impl<'a, 'config> Runtime<'a, 'config> {
         }
     }
 
+    /// Context the runtime executes in.
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    /// Program counter and opcode of the last step.
+    #[inline]
+    pub fn last_step(&self) -> (usize, Option<u8>) {
+        self.machine.last_step()
+    }
+
     #[inline]
     pub fn return_value(self) -> Vec<u8> {
         self.machine.return_value()
@@ -138,6 +158,38 @@ impl<'a, 'config> Runtime<'a, 'config> {
         self.machine.stack_push(value)
     }
 
+    /// Program counter and opcode of the next step, if a tracer may want it.
+    #[cfg(feature = "tracing")]
+    #[inline]
+    fn trace_position(&self) -> Option<(usize, u8)> {
+        self.machine.position()
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    #[inline(always)]
+    fn trace_position(&self) -> Option<(usize, u8)> {
+        None
+    }
+
+    /// Report a step at `position` to the handler, before or after it runs.
+    #[cfg(feature = "tracing")]
+    #[inline]
+    fn trace_step<H: Handler>(&self, handler: &mut H, position: Option<(usize, u8)>, after: bool) {
+        if let Some((pc, opcode)) = position {
+            handler.trace_step(pc, opcode, after);
+        }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    #[inline(always)]
+    fn trace_step<H: Handler>(
+        &self,
+        _handler: &mut H,
+        _position: Option<(usize, u8)>,
+        _after: bool,
+    ) {
+    }
+
     /// Step the runtime.
     pub fn step<'b, H: Handler>(
         &'b mut self,
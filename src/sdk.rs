@@ -14,7 +14,7 @@ mod exports {
         // ###############
         // # Context API #
         // ###############
-        fn current_account_id(register_id: u64);
+        pub(crate) fn current_account_id(register_id: u64);
         fn signer_account_id(register_id: u64);
         fn signer_account_pk(register_id: u64);
         pub(crate) fn predecessor_account_id(register_id: u64);
@@ -23,14 +23,14 @@ mod exports {
         pub(crate) fn block_index() -> u64;
         pub(crate) fn block_timestamp() -> u64;
         fn epoch_height() -> u64;
-        fn storage_usage() -> u64;
+        pub(crate) fn storage_usage() -> u64;
         // #################
         // # Economics API #
         // #################
         fn account_balance(balance_ptr: u64);
-        fn attached_deposit(balance_ptr: u64);
+        pub(crate) fn attached_deposit(balance_ptr: u64);
         fn prepaid_gas() -> u64;
-        fn used_gas() -> u64;
+        pub(crate) fn used_gas() -> u64;
         // ############
         // # Math API #
         // ############
@@ -49,7 +49,7 @@ mod exports {
         // ################
         // # Promises API #
         // ################
-        fn promise_create(
+        pub(crate) fn promise_create(
             account_id_len: u64,
             account_id_ptr: u64,
             method_name_len: u64,
@@ -71,7 +71,7 @@ mod exports {
             gas: u64,
         ) -> u64;
         fn promise_and(promise_idx_ptr: u64, promise_idx_count: u64) -> u64;
-        fn promise_batch_create(account_id_len: u64, account_id_ptr: u64) -> u64;
+        pub(crate) fn promise_batch_create(account_id_len: u64, account_id_ptr: u64) -> u64;
         fn promise_batch_then(promise_index: u64, account_id_len: u64, account_id_ptr: u64) -> u64;
         // #######################
         // # Promise API actions #
@@ -87,7 +87,7 @@ mod exports {
             amount_ptr: u64,
             gas: u64,
         );
-        fn promise_batch_action_transfer(promise_index: u64, amount_ptr: u64);
+        pub(crate) fn promise_batch_action_transfer(promise_index: u64, amount_ptr: u64);
         fn promise_batch_action_stake(
             promise_index: u64,
             amount_ptr: u64,
@@ -140,10 +140,13 @@ mod exports {
         pub(crate) fn storage_read(key_len: u64, key_ptr: u64, register_id: u64) -> u64;
         pub(crate) fn storage_remove(key_len: u64, key_ptr: u64, register_id: u64) -> u64;
         fn storage_has_key(key_len: u64, key_ptr: u64) -> u64;
-        fn storage_iter_prefix(prefix_len: u64, prefix_ptr: u64) -> u64;
+        pub(crate) fn storage_iter_prefix(prefix_len: u64, prefix_ptr: u64) -> u64;
         fn storage_iter_range(start_len: u64, start_ptr: u64, end_len: u64, end_ptr: u64) -> u64;
-        fn storage_iter_next(iterator_id: u64, key_register_id: u64, value_register_id: u64)
-            -> u64;
+        pub(crate) fn storage_iter_next(
+            iterator_id: u64,
+            key_register_id: u64,
+            value_register_id: u64,
+        ) -> u64;
         // ###############
         // # Validator API #
         // ###############
@@ -206,6 +209,43 @@ pub fn remove_storage(key: &[u8]) {
     }
 }
 
+/// Creates an iterator over storage keys starting with `prefix`.
+pub fn storage_iter_prefix(prefix: &[u8]) -> u64 {
+    unsafe { exports::storage_iter_prefix(prefix.len() as u64, prefix.as_ptr() as u64) }
+}
+
+/// Advances the storage iterator, returning the next key.
+pub fn storage_iter_next_key(iterator_id: u64) -> Option<Vec<u8>> {
+    unsafe {
+        if exports::storage_iter_next(iterator_id, 0, 1) == 1 {
+            let bytes: Vec<u8> = vec![0u8; exports::register_len(0) as usize];
+            exports::read_register(0, bytes.as_ptr() as *const u64 as u64);
+            Some(bytes)
+        } else {
+            None
+        }
+    }
+}
+
+/// Storage used by the contract account in bytes.
+pub fn storage_usage() -> u64 {
+    unsafe { exports::storage_usage() }
+}
+
+/// NEAR gas burnt so far by the call.
+pub fn used_gas() -> u64 {
+    unsafe { exports::used_gas() }
+}
+
+/// Amount of yoctoNEAR attached to the call.
+pub fn attached_deposit() -> u128 {
+    unsafe {
+        let data = [0u8; 16];
+        exports::attached_deposit(data.as_ptr() as u64);
+        u128::from_le_bytes(data)
+    }
+}
+
 pub fn block_timestamp() -> u64 {
     unsafe { exports::block_timestamp() }
 }
@@ -220,10 +260,11 @@ pub fn panic() {
 }
 
 #[allow(dead_code)]
-pub fn panic_utf8(bytes: &[u8]) {
+pub fn panic_utf8(bytes: &[u8]) -> ! {
     unsafe {
         exports::panic_utf8(bytes.len() as u64, bytes.as_ptr() as u64);
     }
+    unreachable!()
 }
 
 pub fn log_utf8(bytes: &[u8]) {
@@ -241,6 +282,47 @@ pub fn predecessor_account_id() -> Vec<u8> {
     }
 }
 
+pub fn current_account_id() -> Vec<u8> {
+    unsafe {
+        exports::current_account_id(1);
+        let bytes: Vec<u8> = vec![0u8; exports::register_len(1) as usize];
+        exports::read_register(1, bytes.as_ptr() as *const u64 as u64);
+        bytes
+    }
+}
+
+/// Creates a promise calling `method_name` on `account_id`.
+pub fn promise_create(
+    account_id: &[u8],
+    method_name: &[u8],
+    arguments: &[u8],
+    amount: u128,
+    gas: u64,
+) -> u64 {
+    unsafe {
+        exports::promise_create(
+            account_id.len() as _,
+            account_id.as_ptr() as _,
+            method_name.len() as _,
+            method_name.as_ptr() as _,
+            arguments.len() as _,
+            arguments.as_ptr() as _,
+            &amount as *const u128 as _,
+            gas,
+        )
+    }
+}
+
+/// Creates a promise transferring `amount` of yoctoNEAR to `account_id`.
+pub fn promise_transfer(account_id: &[u8], amount: u128) -> u64 {
+    unsafe {
+        let promise_id =
+            exports::promise_batch_create(account_id.len() as _, account_id.as_ptr() as _);
+        exports::promise_batch_action_transfer(promise_id, &amount as *const u128 as _);
+        promise_id
+    }
+}
+
 /// Calls environment keccak256 on given data.
 pub fn keccak(data: &[u8]) -> H256 {
     unsafe {
@@ -250,10 +332,3 @@ pub fn keccak(data: &[u8]) -> H256 {
         bytes
     }
 }
-
-/// Calls environment panic with data encoded in hex as panic message.
-pub fn panic_hex(data: &[u8]) -> ! {
-    let message = crate::types::bytes_to_hex(data).into_bytes();
-    unsafe { exports::panic_utf8(message.len() as _, message.as_ptr() as _) }
-    unreachable!()
-}
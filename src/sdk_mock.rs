@@ -0,0 +1,213 @@
+//! In-memory stand-in for `sdk`, so that `near_backend` can be unit tested
+//! on the host. Only compiled for tests.
+//!
+//! State is per thread, and tests start from a clean one with `reset`.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::vec::Vec;
+
+/// Extra bytes NEAR counts for each storage record.
+const STORAGE_RECORD_BYTES: u64 = 40;
+
+/// A promise created by the contract.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Promise {
+    Transfer {
+        account_id: Vec<u8>,
+        amount: u128,
+    },
+    FunctionCall {
+        account_id: Vec<u8>,
+        method_name: Vec<u8>,
+        arguments: Vec<u8>,
+        amount: u128,
+    },
+}
+
+struct Context {
+    input: Vec<u8>,
+    output: Option<Vec<u8>>,
+    storage: BTreeMap<Vec<u8>, Vec<u8>>,
+    /// Items of each iterator, left to be returned.
+    iterators: Vec<Vec<(Vec<u8>, Vec<u8>)>>,
+    attached_deposit: u128,
+    predecessor_account_id: Vec<u8>,
+    current_account_id: Vec<u8>,
+    block_index: u64,
+    promises: Vec<Promise>,
+    logs: Vec<Vec<u8>>,
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Self {
+            input: Vec::new(),
+            output: None,
+            storage: BTreeMap::new(),
+            iterators: Vec::new(),
+            attached_deposit: 0,
+            predecessor_account_id: b"alice.near".to_vec(),
+            current_account_id: b"evm.near".to_vec(),
+            block_index: 1,
+            promises: Vec::new(),
+            logs: Vec::new(),
+        }
+    }
+}
+
+thread_local! {
+    static CONTEXT: RefCell<Context> = RefCell::new(Context::default());
+}
+
+fn with<T>(f: impl FnOnce(&mut Context) -> T) -> T {
+    CONTEXT.with(|context| f(&mut context.borrow_mut()))
+}
+
+/// Clears storage, promises and logs, and restores the default accounts.
+pub fn reset() {
+    with(|context| *context = Context::default());
+}
+
+pub fn set_input(input: &[u8]) {
+    with(|context| context.input = input.to_vec());
+}
+
+pub fn set_attached_deposit(amount: u128) {
+    with(|context| context.attached_deposit = amount);
+}
+
+pub fn set_predecessor_account_id(account_id: &[u8]) {
+    with(|context| context.predecessor_account_id = account_id.to_vec());
+}
+
+/// Output of the last `return_output`.
+pub fn output() -> Option<Vec<u8>> {
+    with(|context| context.output.clone())
+}
+
+/// All storage keys, in order.
+pub fn storage_keys() -> Vec<Vec<u8>> {
+    with(|context| context.storage.keys().cloned().collect())
+}
+
+/// Promises created since the last `reset`, in order.
+pub fn promises() -> Vec<Promise> {
+    with(|context| context.promises.clone())
+}
+
+/// Logs since the last `reset`, in order.
+pub fn logs() -> Vec<Vec<u8>> {
+    with(|context| context.logs.clone())
+}
+
+pub fn read_input() -> Vec<u8> {
+    with(|context| context.input.clone())
+}
+
+pub fn read_input_arr20() -> [u8; 20] {
+    let mut bytes = [0u8; 20];
+    bytes.copy_from_slice(&read_input()[..20]);
+    bytes
+}
+
+pub fn return_output(value: &[u8]) {
+    with(|context| context.output = Some(value.to_vec()));
+}
+
+pub fn read_storage(key: &[u8]) -> Option<Vec<u8>> {
+    with(|context| context.storage.get(key).cloned())
+}
+
+pub fn write_storage(key: &[u8], value: &[u8]) {
+    with(|context| context.storage.insert(key.to_vec(), value.to_vec()));
+}
+
+pub fn remove_storage(key: &[u8]) {
+    with(|context| context.storage.remove(key));
+}
+
+pub fn storage_iter_prefix(prefix: &[u8]) -> u64 {
+    with(|context| {
+        let mut items: Vec<_> = context
+            .storage
+            .range(prefix.to_vec()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        items.reverse();
+        context.iterators.push(items);
+        (context.iterators.len() - 1) as u64
+    })
+}
+
+pub fn storage_iter_next_key(iterator_id: u64) -> Option<Vec<u8>> {
+    with(|context| context.iterators[iterator_id as usize].pop()).map(|(key, _)| key)
+}
+
+pub fn storage_usage() -> u64 {
+    with(|context| {
+        context
+            .storage
+            .iter()
+            .map(|(key, value)| key.len() as u64 + value.len() as u64 + STORAGE_RECORD_BYTES)
+            .sum()
+    })
+}
+
+pub fn attached_deposit() -> u128 {
+    with(|context| context.attached_deposit)
+}
+
+pub fn block_timestamp() -> u64 {
+    0
+}
+
+pub fn block_index() -> u64 {
+    with(|context| context.block_index)
+}
+
+pub fn panic_utf8(bytes: &[u8]) -> ! {
+    panic!("{}", String::from_utf8_lossy(bytes))
+}
+
+pub fn log_utf8(bytes: &[u8]) {
+    with(|context| context.logs.push(bytes.to_vec()));
+}
+
+pub fn predecessor_account_id() -> Vec<u8> {
+    with(|context| context.predecessor_account_id.clone())
+}
+
+pub fn current_account_id() -> Vec<u8> {
+    with(|context| context.current_account_id.clone())
+}
+
+pub fn promise_create(
+    account_id: &[u8],
+    method_name: &[u8],
+    arguments: &[u8],
+    amount: u128,
+    _gas: u64,
+) -> u64 {
+    push_promise(Promise::FunctionCall {
+        account_id: account_id.to_vec(),
+        method_name: method_name.to_vec(),
+        arguments: arguments.to_vec(),
+        amount,
+    })
+}
+
+pub fn promise_transfer(account_id: &[u8], amount: u128) -> u64 {
+    push_promise(Promise::Transfer {
+        account_id: account_id.to_vec(),
+        amount,
+    })
+}
+
+fn push_promise(promise: Promise) -> u64 {
+    with(|context| {
+        context.promises.push(promise);
+        (context.promises.len() - 1) as u64
+    })
+}
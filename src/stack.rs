@@ -18,11 +18,14 @@ use core::convert::Infallible;
 use primitive_types::{H160, H256, U256};
 
 use crate::backend::{Apply, Backend, Basic, Log};
+use crate::precompiles::{PrecompileOutput, PrecompileResult};
 use crate::runtime::Machine;
 use crate::runtime::{
-    Capture, Config, Context, CreateScheme, ExitError, ExitReason, ExitSucceed, ExternalOpcode,
+    CallScheme, Capture, Config, Context, CreateScheme, ExitError, ExitReason, ExternalOpcode,
     Handler, Opcode, Runtime, Stack, Transfer,
 };
+#[cfg(feature = "tracing")]
+use crate::tracer::{Frame, FrameKind, Step, Tracer};
 
 /// Account definition for the stack-based executor.
 #[derive(Default, Clone, Debug, Eq, PartialEq)]
@@ -38,6 +41,50 @@ pub struct StackAccount {
     pub reset_storage: bool,
 }
 
+/// Previous and new value of a state field.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_crate::Serialize, serde_crate::Deserialize),
+    serde(crate = "serde_crate")
+)]
+pub struct Change<T> {
+    pub from: T,
+    pub to: T,
+}
+
+/// Changes to an account made by a transaction.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_crate::Serialize, serde_crate::Deserialize),
+    serde(crate = "serde_crate")
+)]
+pub struct AccountDiff {
+    pub nonce: Change<U256>,
+    pub balance: Change<U256>,
+    pub code: Change<Vec<u8>>,
+    /// Written storage slots whose value changed.
+    pub storage: BTreeMap<H256, Change<H256>>,
+    /// Whether all storage was removed. Removed slots which are not
+    /// written again are not listed in `storage`.
+    pub reset_storage: bool,
+    pub deleted: bool,
+}
+
+/// Changed accounts by address.
+pub type StateDiff = BTreeMap<H160, AccountDiff>;
+
+/// Where a frame exited with an error.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ErrorLocation {
+    /// Account whose code was running.
+    pub address: H160,
+    pub pc: usize,
+    /// Opcode at `pc`, `None` past the end of the code.
+    pub opcode: Option<u8>,
+}
+
 pub enum StackExitKind {
     Succeeded,
     Reverted,
@@ -57,8 +104,11 @@ pub struct StackExecutor<'backend, 'machine, 'config, B> {
     backend: &'backend B,
     machine: &'machine dyn Machine,
     config: &'config Config,
-    precompile: fn(H160, &[u8], &Context) -> Option<Result<(ExitSucceed, Vec<u8>), ExitError>>,
+    precompile: fn(H160, &[u8], &Context) -> Option<PrecompileResult>,
     substates: Vec<StackSubstate>,
+    error_location: Option<ErrorLocation>,
+    #[cfg(feature = "tracing")]
+    tracer: Option<&'backend mut dyn Tracer>,
 }
 
 impl<'backend, 'machine, 'config, B: Backend> StackExecutor<'backend, 'machine, 'config, B> {
@@ -67,7 +117,7 @@ impl<'backend, 'machine, 'config, B: Backend> StackExecutor<'backend, 'machine,
         backend: &'backend B,
         machine: &'machine dyn Machine,
         config: &'config Config,
-        precompile: fn(H160, &[u8], &Context) -> Option<Result<(ExitSucceed, Vec<u8>), ExitError>>,
+        precompile: fn(H160, &[u8], &Context) -> Option<PrecompileResult>,
     ) -> Self {
         Self {
             backend,
@@ -81,9 +131,18 @@ impl<'backend, 'machine, 'config, B: Backend> StackExecutor<'backend, 'machine,
                 is_static: false,
                 depth: None,
             }],
+            error_location: None,
+            #[cfg(feature = "tracing")]
+            tracer: None,
         }
     }
 
+    /// Report execution of the following transactions to `tracer`.
+    #[cfg(feature = "tracing")]
+    pub fn set_tracer(&mut self, tracer: &'backend mut dyn Tracer) {
+        self.tracer = Some(tracer);
+    }
+
     /// Create a substate executor from the current executor.
     pub fn enter_substate(&mut self, is_static: bool) {
         let parent = self.substates.last().unwrap();
@@ -109,10 +168,9 @@ impl<'backend, 'machine, 'config, B: Backend> StackExecutor<'backend, 'machine,
         let mut exited = self.substates.pop().unwrap();
         let parent = self.substates.last_mut().unwrap();
 
-        parent.logs.append(&mut exited.logs);
-
         match kind {
             StackExitKind::Succeeded => {
+                parent.logs.append(&mut exited.logs);
                 parent.deleted.append(&mut exited.deleted);
                 parent.state.append(&mut exited.state);
                 // parent.gasometer.record_stipend(exited.gasometer.gas())?;
@@ -129,10 +187,29 @@ impl<'backend, 'machine, 'config, B: Backend> StackExecutor<'backend, 'machine,
 
     /// Execute the runtime until it returns.
     pub fn execute(&mut self, runtime: &mut Runtime) -> ExitReason {
-        match runtime.run(self) {
+        let reason = match runtime.run(self) {
             Capture::Exit(s) => s,
             Capture::Trap(_) => panic!(),
-        }
+        };
+        self.error_location = match reason {
+            ExitReason::Error(_) | ExitReason::Fatal(_) => {
+                let (pc, opcode) = runtime.last_step();
+                Some(ErrorLocation {
+                    address: runtime.context().address,
+                    pc,
+                    opcode,
+                })
+            }
+            _ => None,
+        };
+        reason
+    }
+
+    /// Where the frame executed last exited with an error, if it did. After a
+    /// failed transaction this is where its outermost frame failed, or `None`
+    /// if it failed outside of running code, e.g. with `OutOfFund`.
+    pub fn error_location(&self) -> Option<&ErrorLocation> {
+        self.error_location.as_ref()
     }
 
     /// Get remaining gas.
@@ -143,22 +220,27 @@ impl<'backend, 'machine, 'config, B: Backend> StackExecutor<'backend, 'machine,
         //     .gasometer.gas()
     }
 
-    /// Execute a `CREATE` transaction.
-    pub fn transact_create(&mut self, caller: H160, value: U256, init_code: Vec<u8>) -> ExitReason {
+    /// Execute a `CREATE` transaction. Returns revert data if it reverted.
+    pub fn transact_create(
+        &mut self,
+        caller: H160,
+        value: U256,
+        init_code: Vec<u8>,
+    ) -> (ExitReason, Vec<u8>) {
         match self.create_inner(caller, CreateScheme::Legacy { caller }, value, init_code) {
-            Capture::Exit((s, _, _)) => s,
+            Capture::Exit((s, _, v)) => (s, v),
             Capture::Trap(_) => panic!(),
         }
     }
 
-    /// Execute a `CREATE2` transaction.
+    /// Execute a `CREATE2` transaction. Returns revert data if it reverted.
     pub fn transact_create2(
         &mut self,
         caller: H160,
         value: U256,
         init_code: Vec<u8>,
         salt: H256,
-    ) -> ExitReason {
+    ) -> (ExitReason, Vec<u8>) {
         let code_hash = crate::types::keccak(&init_code);
 
         match self.create_inner(
@@ -171,7 +253,7 @@ impl<'backend, 'machine, 'config, B: Backend> StackExecutor<'backend, 'machine,
             value,
             init_code,
         ) {
-            Capture::Exit((s, _, _)) => s,
+            Capture::Exit((s, _, v)) => (s, v),
             Capture::Trap(_) => unreachable!(),
         }
     }
@@ -200,7 +282,7 @@ impl<'backend, 'machine, 'config, B: Backend> StackExecutor<'backend, 'machine,
                 value,
             }),
             data,
-            false,
+            CallScheme::Call,
             context,
         ) {
             Capture::Exit((s, v)) => (s, v),
@@ -252,6 +334,77 @@ impl<'backend, 'machine, 'config, B: Backend> StackExecutor<'backend, 'machine,
         (applies, logs)
     }
 
+    /// Changes made to the backend state, with previous values read from
+    /// the backend. `None` while a call is in progress, as its changes are
+    /// not final yet.
+    pub fn state_diff(&self) -> Option<StateDiff> {
+        if self.substates.len() != 1 {
+            return None;
+        }
+
+        let current = self.substates.last().unwrap();
+        // Deleted accounts are usually modified too.
+        let addresses: BTreeSet<H160> = current
+            .state
+            .keys()
+            .chain(current.deleted.iter())
+            .copied()
+            .collect();
+        let mut diff = StateDiff::new();
+        for address in addresses {
+            let basic = self.backend.basic(address);
+            let code = self.backend.code(address);
+            let deleted = current.deleted.contains(&address);
+            let account = current.state.get(&address);
+            let mut account_diff = AccountDiff {
+                nonce: Change {
+                    from: basic.nonce,
+                    to: basic.nonce,
+                },
+                balance: Change {
+                    from: basic.balance,
+                    to: basic.balance,
+                },
+                code: Change {
+                    from: code.clone(),
+                    to: code,
+                },
+                storage: BTreeMap::new(),
+                reset_storage: deleted,
+                deleted,
+            };
+            if deleted {
+                account_diff.nonce.to = U256::zero();
+                account_diff.balance.to = U256::zero();
+                account_diff.code.to = Vec::new();
+            } else if let Some(account) = account {
+                account_diff.nonce.to = account.basic.nonce;
+                account_diff.balance.to = account.basic.balance;
+                if let Some(code) = &account.code {
+                    account_diff.code.to = code.clone();
+                }
+                account_diff.reset_storage = account.reset_storage;
+                for (index, value) in account.storage.iter() {
+                    let from = self.backend.storage(address, *index);
+                    if from != *value {
+                        account_diff
+                            .storage
+                            .insert(*index, Change { from, to: *value });
+                    }
+                }
+            }
+            if account_diff.nonce.from != account_diff.nonce.to
+                || account_diff.balance.from != account_diff.balance.to
+                || account_diff.code.from != account_diff.code.to
+                || !account_diff.storage.is_empty()
+                || account_diff.reset_storage
+            {
+                diff.insert(address, account_diff);
+            }
+        }
+        Some(diff)
+    }
+
     /// Get account reference.
     pub fn account(&self, address: H160) -> Option<&StackAccount> {
         for substate in self.substates.iter().rev() {
@@ -364,6 +517,21 @@ impl<'backend, 'machine, 'config, B: Backend> StackExecutor<'backend, 'machine,
         scheme: CreateScheme,
         value: U256,
         init_code: Vec<u8>,
+    ) -> Capture<(ExitReason, Option<H160>, Vec<u8>), Infallible> {
+        #[cfg(feature = "tracing")]
+        self.trace_create_enter(caller, scheme, value, &init_code);
+        let result = self.create_frame(caller, scheme, value, init_code);
+        #[cfg(feature = "tracing")]
+        self.trace_create_exit(&result);
+        result
+    }
+
+    fn create_frame(
+        &mut self,
+        caller: H160,
+        scheme: CreateScheme,
+        value: U256,
+        init_code: Vec<u8>,
     ) -> Capture<(ExitReason, Option<H160>, Vec<u8>), Infallible> {
         macro_rules! try_or_fail {
             ( $e:expr ) => {
@@ -396,13 +564,11 @@ impl<'backend, 'machine, 'config, B: Backend> StackExecutor<'backend, 'machine,
                     return Capture::Exit((ExitError::CreateCollision.into(), None, Vec::new()));
                 }
             } else {
-                let code = self.backend.code(address);
-                self.account_mut(address).code = Some(code.clone());
-
-                if !code.is_empty() {
+                if self.backend.code_size(address) != 0 {
                     let _ = self.exit_substate(StackExitKind::Failed);
                     return Capture::Exit((ExitError::CreateCollision.into(), None, Vec::new()));
                 }
+                self.account_mut(address).code = Some(Vec::new());
             }
 
             if self.nonce(address) > U256::zero() {
@@ -482,6 +648,23 @@ impl<'backend, 'machine, 'config, B: Backend> StackExecutor<'backend, 'machine,
     }
 
     fn call_inner(
+        &mut self,
+        code_address: H160,
+        transfer: Option<Transfer>,
+        input: Vec<u8>,
+        scheme: CallScheme,
+        context: Context,
+    ) -> Capture<(ExitReason, Vec<u8>), Infallible> {
+        #[cfg(feature = "tracing")]
+        self.trace_call_enter(code_address, transfer.as_ref(), &input, scheme, &context);
+        let is_static = scheme == CallScheme::StaticCall;
+        let result = self.call_frame(code_address, transfer, input, is_static, context);
+        #[cfg(feature = "tracing")]
+        self.trace_call_exit(&result);
+        result
+    }
+
+    fn call_frame(
         &mut self,
         code_address: H160,
         transfer: Option<Transfer>,
@@ -513,9 +696,19 @@ impl<'backend, 'machine, 'config, B: Backend> StackExecutor<'backend, 'machine,
 
         if let Some(ret) = (self.precompile)(code_address, &input, &context) {
             return match ret {
-                Ok((s, out)) => {
+                Ok(PrecompileOutput {
+                    exit_status,
+                    output,
+                    logs,
+                    burn,
+                }) => {
+                    if let Err(e) = self.withdraw(context.address, burn) {
+                        let _ = self.exit_substate(StackExitKind::Failed);
+                        return Capture::Exit((ExitReason::Error(e), Vec::new()));
+                    }
+                    self.substates.last_mut().unwrap().logs.extend(logs);
                     let _ = self.exit_substate(StackExitKind::Succeeded);
-                    Capture::Exit((ExitReason::Succeed(s), out))
+                    Capture::Exit((ExitReason::Succeed(exit_status), output))
                 }
                 Err(e) => {
                     let _ = self.exit_substate(StackExitKind::Failed);
@@ -555,6 +748,100 @@ impl<'backend, 'machine, 'config, B: Backend> StackExecutor<'backend, 'machine,
     }
 }
 
+#[cfg(feature = "tracing")]
+impl<'backend, 'machine, 'config, B: Backend> StackExecutor<'backend, 'machine, 'config, B> {
+    /// Depth of the frame being executed.
+    fn trace_depth(&self) -> usize {
+        self.substates.last().unwrap().depth.unwrap_or(0)
+    }
+
+    fn trace_enter(&mut self, kind: FrameKind, from: H160, to: H160, value: U256, input: &[u8]) {
+        let depth = match self.substates.last().unwrap().depth {
+            None => 0,
+            Some(n) => n + 1,
+        };
+        let gas = self.gas_left();
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.enter(&Frame {
+                kind,
+                depth,
+                from,
+                to,
+                value,
+                input,
+                gas,
+            });
+        }
+    }
+
+    fn trace_create_enter(
+        &mut self,
+        caller: H160,
+        scheme: CreateScheme,
+        value: U256,
+        init_code: &[u8],
+    ) {
+        if self.tracer.is_none() {
+            return;
+        }
+        let kind = match scheme {
+            CreateScheme::Create2 { .. } => FrameKind::Create2,
+            _ => FrameKind::Create,
+        };
+        let address = self.create_address(scheme);
+        self.trace_enter(kind, caller, address, value, init_code);
+    }
+
+    fn trace_create_exit(
+        &mut self,
+        result: &Capture<(ExitReason, Option<H160>, Vec<u8>), Infallible>,
+    ) {
+        if self.tracer.is_none() {
+            return;
+        }
+        let (reason, output) = match result {
+            Capture::Exit((reason, Some(address), _)) => (reason, self.code(*address)),
+            Capture::Exit((reason, None, output)) => (reason, output.clone()),
+            Capture::Trap(trap) => match *trap {},
+        };
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.exit(reason, &output);
+        }
+    }
+
+    fn trace_call_enter(
+        &mut self,
+        code_address: H160,
+        transfer: Option<&Transfer>,
+        input: &[u8],
+        scheme: CallScheme,
+        context: &Context,
+    ) {
+        if self.tracer.is_none() {
+            return;
+        }
+        // The callee code of CALLCODE and DELEGATECALL runs as the caller.
+        let (kind, from) = match scheme {
+            CallScheme::Call => (FrameKind::Call, context.caller),
+            CallScheme::CallCode => (FrameKind::CallCode, context.address),
+            CallScheme::DelegateCall => (FrameKind::DelegateCall, context.address),
+            CallScheme::StaticCall => (FrameKind::StaticCall, context.caller),
+        };
+        let value = transfer.map(|transfer| transfer.value).unwrap_or_default();
+        self.trace_enter(kind, from, code_address, value, input);
+    }
+
+    fn trace_call_exit(&mut self, result: &Capture<(ExitReason, Vec<u8>), Infallible>) {
+        let (reason, output) = match result {
+            Capture::Exit((reason, output)) => (reason, output),
+            Capture::Trap(trap) => match *trap {},
+        };
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.exit(reason, output);
+        }
+    }
+}
+
 impl<'backend, 'machine, 'config, B: Backend> Handler
     for StackExecutor<'backend, 'machine, 'config, B>
 {
@@ -620,14 +907,14 @@ impl<'backend, 'machine, 'config, B: Backend> Handler
         let value = self
             .account(address)
             .and_then(|v| v.code.as_ref().map(|c| crate::types::keccak(&c)))
-            .unwrap_or(self.backend.code_hash(address));
+            .unwrap_or_else(|| self.backend.code_hash(address));
         value
     }
 
     fn code(&self, address: H160) -> Vec<u8> {
         self.account(address)
             .and_then(|v| v.code.clone())
-            .unwrap_or(self.backend.code(address))
+            .unwrap_or_else(|| self.backend.code(address))
     }
 
     fn storage(&self, address: H160, index: H256) -> H256 {
@@ -641,7 +928,7 @@ impl<'backend, 'machine, 'config, B: Backend> Handler
                     s
                 }
             })
-            .unwrap_or(self.backend.storage(address, index))
+            .unwrap_or_else(|| self.backend.storage(address, index))
     }
 
     fn original_storage(&self, address: H160, index: H256) -> H256 {
@@ -693,11 +980,11 @@ impl<'backend, 'machine, 'config, B: Backend> Handler
                 account.basic.nonce != U256::zero()
                     || account.basic.balance != U256::zero()
                     || account.code.as_ref().map(|c| c.len() != 0).unwrap_or(false)
-                    || self.backend.code(address).len() != 0
+                    || self.backend.code_size(address) != 0
             } else {
                 self.backend.basic(address).nonce != U256::zero()
                     || self.backend.basic(address).balance != U256::zero()
-                    || self.backend.code(address).len() != 0
+                    || self.backend.code_size(address) != 0
             }
         }
     }
@@ -761,10 +1048,10 @@ impl<'backend, 'machine, 'config, B: Backend> Handler
         transfer: Option<Transfer>,
         input: Vec<u8>,
         _target_gas: Option<usize>,
-        is_static: bool,
+        scheme: CallScheme,
         context: Context,
     ) -> Capture<(ExitReason, Vec<u8>), Self::CallInterrupt> {
-        self.call_inner(code_address, transfer, input, is_static, context)
+        self.call_inner(code_address, transfer, input, scheme, context)
     }
 
     fn pre_validate(
@@ -775,4 +1062,268 @@ impl<'backend, 'machine, 'config, B: Backend> Handler
     ) -> Result<(), ExitError> {
         Ok(())
     }
+
+    #[cfg(feature = "tracing")]
+    fn trace_step(&mut self, pc: usize, opcode: u8, after: bool) {
+        if self.tracer.is_none() {
+            return;
+        }
+        let depth = self.trace_depth();
+        let gas = self.gas_left();
+        let stack = self.machine.stack_data();
+        let step = Step {
+            depth,
+            pc,
+            opcode,
+            stack: &stack,
+            memory_size: self.machine.memory_size(),
+            gas,
+        };
+        if let Some(tracer) = self.tracer.as_mut() {
+            if after {
+                tracer.after_step(&step);
+            } else {
+                tracer.before_step(&step);
+            }
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    fn trace_sload(&mut self, address: H160, index: H256, value: H256) {
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.sload(address, index, value);
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    fn trace_sstore(&mut self, address: H160, index: H256, value: H256) {
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.sstore(address, index, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{MemoryAccount, MemoryBackend, MemoryVicinity};
+    use crate::runner::Runner;
+    use crate::types::{u256_to_arr, ExitSucceed, StateDiffResult, TransactionStatus};
+    use borsh::{BorshDeserialize, BorshSerialize};
+
+    #[test]
+    fn test_reverted_logs() {
+        let caller = H160::from_low_u64_be(1);
+        let succeeding = H160::from_low_u64_be(2);
+        let reverting = H160::from_low_u64_be(3);
+        let mut state = BTreeMap::new();
+        let mut code = Vec::new();
+        for callee in [succeeding, reverting].iter() {
+            // CALL(gas, callee, 0, 0, 0, 0, 0) POP
+            code.extend_from_slice(&[0x60, 0x00, 0x80, 0x80, 0x80, 0x80, 0x60]);
+            code.extend_from_slice(&[callee.to_low_u64_be() as u8, 0x5a, 0xf1, 0x50]);
+        }
+        code.push(0x00);
+        state.insert(
+            caller,
+            MemoryAccount {
+                code,
+                ..Default::default()
+            },
+        );
+        state.insert(
+            succeeding,
+            MemoryAccount {
+                // LOG0(0, 0) STOP
+                code: vec![0x60, 0x00, 0x80, 0xa0, 0x00],
+                ..Default::default()
+            },
+        );
+        state.insert(
+            reverting,
+            MemoryAccount {
+                // LOG0(0, 0) REVERT(0, 0)
+                code: vec![0x60, 0x00, 0x80, 0xa0, 0x60, 0x00, 0x80, 0xfd],
+                ..Default::default()
+            },
+        );
+        let vicinity = MemoryVicinity::default();
+        let mut backend = MemoryBackend::new(&vicinity, state);
+        let execution =
+            Runner::execute(&mut backend, U256::zero(), false, false, None, |executor| {
+                executor.transact_call(H160::zero(), caller, U256::zero(), Vec::new())
+            });
+
+        assert!(execution.reason.is_succeed());
+        let addresses: Vec<H160> = execution.logs.iter().map(|log| log.address).collect();
+        assert_eq!(addresses, vec![succeeding]);
+    }
+
+    #[test]
+    fn test_state_diff() {
+        let caller = H160::from_low_u64_be(1);
+        let contract = H160::from_low_u64_be(2);
+        let mut state = BTreeMap::new();
+        state.insert(
+            caller,
+            MemoryAccount {
+                nonce: U256::from(3),
+                balance: U256::from(100),
+                ..Default::default()
+            },
+        );
+        let mut storage = BTreeMap::new();
+        storage.insert(H256::from_low_u64_be(1), H256::from_low_u64_be(7));
+        storage.insert(H256::from_low_u64_be(2), H256::from_low_u64_be(8));
+        state.insert(
+            contract,
+            MemoryAccount {
+                storage,
+                // SSTORE(0, 42) SSTORE(1, 0) SSTORE(2, 8) STOP
+                code: vec![
+                    0x60, 0x2a, 0x60, 0x00, 0x55, 0x60, 0x00, 0x60, 0x01, 0x55, 0x60, 0x08, 0x60,
+                    0x02, 0x55, 0x00,
+                ],
+                ..Default::default()
+            },
+        );
+        let vicinity = MemoryVicinity::default();
+        let mut backend = MemoryBackend::new(&vicinity, state);
+        let execution =
+            Runner::execute(&mut backend, U256::zero(), false, true, None, |executor| {
+                executor.transact_call(caller, contract, U256::from(10), Vec::new())
+            });
+        assert!(execution.reason.is_succeed());
+
+        let state_diff = execution.state_diff.unwrap();
+        assert_eq!(state_diff.len(), 2);
+        let diff = &state_diff[&caller];
+        let change = |from: u64, to: u64| Change {
+            from: U256::from(from),
+            to: U256::from(to),
+        };
+        assert_eq!(diff.nonce, change(3, 4));
+        assert_eq!(diff.balance, change(100, 90));
+        assert!(diff.storage.is_empty());
+        let diff = &state_diff[&contract];
+        assert_eq!(diff.nonce, change(0, 0));
+        assert_eq!(diff.balance, change(0, 10));
+        assert_eq!(diff.code.from, diff.code.to);
+        // The unchanged slot 2 is left out.
+        let storage: Vec<_> = diff
+            .storage
+            .iter()
+            .map(|(key, change)| {
+                (
+                    key.to_low_u64_be(),
+                    change.from.to_low_u64_be(),
+                    change.to.to_low_u64_be(),
+                )
+            })
+            .collect();
+        assert_eq!(storage, vec![(0, 0, 42), (1, 7, 0)]);
+        assert!(!diff.reset_storage && !diff.deleted);
+
+        let result = StateDiffResult::new(TransactionStatus::Succeed, Vec::new(), state_diff);
+        assert_eq!(result.state_diff[0].nonce.1, u256_to_arr(&U256::from(4)));
+        assert_eq!(result.state_diff[1].code, None);
+        let bytes = result.try_to_vec().unwrap();
+        assert_eq!(StateDiffResult::try_from_slice(&bytes).unwrap(), result);
+
+        // No diff while a call is in progress.
+        Runner::execute(&mut backend, U256::zero(), false, false, None, |executor| {
+            assert!(executor.state_diff().is_some());
+            executor.enter_substate(false);
+            assert!(executor.state_diff().is_none());
+            executor.exit_substate(StackExitKind::Reverted).unwrap();
+            (ExitReason::Succeed(ExitSucceed::Stopped), ())
+        });
+    }
+
+    #[test]
+    fn test_state_diff_selfdestruct() {
+        let caller = H160::from_low_u64_be(1);
+        let contract = H160::from_low_u64_be(2);
+        let beneficiary = H160::from_low_u64_be(3);
+        let mut state = BTreeMap::new();
+        state.insert(
+            caller,
+            MemoryAccount {
+                balance: U256::from(100),
+                ..Default::default()
+            },
+        );
+        let mut storage = BTreeMap::new();
+        storage.insert(H256::from_low_u64_be(1), H256::from_low_u64_be(7));
+        // SELFDESTRUCT(beneficiary)
+        let mut code = vec![0x73];
+        code.extend_from_slice(beneficiary.as_bytes());
+        code.push(0xff);
+        state.insert(
+            contract,
+            MemoryAccount {
+                nonce: U256::one(),
+                balance: U256::from(5),
+                storage,
+                code: code.clone(),
+            },
+        );
+        let vicinity = MemoryVicinity::default();
+        let mut backend = MemoryBackend::new(&vicinity, state);
+        let execution =
+            Runner::execute(&mut backend, U256::zero(), false, true, None, |executor| {
+                executor.transact_call(caller, contract, U256::from(10), Vec::new())
+            });
+        assert!(execution.reason.is_succeed());
+
+        let state_diff = execution.state_diff.unwrap();
+        assert_eq!(state_diff.len(), 3);
+        let diff = &state_diff[&contract];
+        assert!(diff.deleted && diff.reset_storage);
+        assert_eq!(diff.nonce.to, U256::zero());
+        assert_eq!(diff.balance.from, U256::from(5));
+        assert_eq!(diff.balance.to, U256::zero());
+        assert_eq!(diff.code.from, code);
+        assert!(diff.code.to.is_empty());
+        assert!(diff.storage.is_empty());
+        assert_eq!(state_diff[&beneficiary].balance.to, U256::from(15));
+    }
+
+    #[test]
+    fn test_state_diff_reset_storage() {
+        let caller = H160::from_low_u64_be(1);
+        let mut stream = rlp::RlpStream::new_list(2);
+        stream.append(&caller);
+        stream.append(&U256::zero());
+        let created: H160 = crate::types::keccak(&stream.out()).into();
+        // Storage left over at the address, without code or nonce.
+        let mut storage = BTreeMap::new();
+        storage.insert(H256::from_low_u64_be(1), H256::from_low_u64_be(7));
+        storage.insert(H256::from_low_u64_be(2), H256::from_low_u64_be(8));
+        let mut state = BTreeMap::new();
+        state.insert(
+            created,
+            MemoryAccount {
+                storage,
+                ..Default::default()
+            },
+        );
+        let vicinity = MemoryVicinity::default();
+        let mut backend = MemoryBackend::new(&vicinity, state);
+        let execution =
+            Runner::execute(&mut backend, U256::zero(), false, true, None, |executor| {
+                // SSTORE(1, 5) STOP
+                let init_code = vec![0x60, 0x05, 0x60, 0x01, 0x55, 0x00];
+                executor.transact_create(caller, U256::zero(), init_code)
+            });
+        assert!(execution.reason.is_succeed());
+
+        let diff = &execution.state_diff.unwrap()[&created];
+        assert!(diff.reset_storage && !diff.deleted);
+        // Slot 2 is removed by the reset, without a change of its own.
+        assert_eq!(diff.storage.len(), 1);
+        let change = &diff.storage[&H256::from_low_u64_be(1)];
+        assert_eq!(change.from, H256::from_low_u64_be(7));
+        assert_eq!(change.to, H256::from_low_u64_be(5));
+    }
 }
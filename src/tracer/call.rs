@@ -0,0 +1,232 @@
+//! Call tree tracing, in the format of geth's `callTracer`.
+
+#[cfg(not(feature = "std"))]
+use alloc::{format, string::String, vec::Vec};
+#[cfg(feature = "std")]
+use std::{format, string::String, vec::Vec};
+
+use primitive_types::{H160, U256};
+
+use super::{exit_error, json_string, ExitReason, Frame, FrameKind, Step, Tracer};
+use crate::types::{bytes_to_hex, decode_revert_reason};
+
+/// A call or create frame with the frames it made.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CallFrame {
+    pub kind: FrameKind,
+    pub from: H160,
+    /// Account whose code ran, or the address being created.
+    pub to: H160,
+    pub value: U256,
+    /// Gas left according to the backend when the frame was entered.
+    pub gas: U256,
+    /// Always zero, see the crate docs on gas.
+    pub gas_used: U256,
+    pub input: Vec<u8>,
+    /// Return data, or the deployed code of a successful create.
+    pub output: Vec<u8>,
+    pub error: Option<String>,
+    /// Message of an `Error(string)` revert.
+    pub revert_reason: Option<String>,
+    pub calls: Vec<CallFrame>,
+}
+
+impl CallFrame {
+    /// The frame as a geth `callTracer` JSON object.
+    pub fn to_json(&self) -> String {
+        let mut result = String::from("{\"type\":\"");
+        result.push_str(match self.kind {
+            FrameKind::Call => "CALL",
+            FrameKind::CallCode => "CALLCODE",
+            FrameKind::DelegateCall => "DELEGATECALL",
+            FrameKind::StaticCall => "STATICCALL",
+            FrameKind::Create => "CREATE",
+            FrameKind::Create2 => "CREATE2",
+        });
+        result.push_str("\",\"from\":\"0x");
+        result.push_str(&bytes_to_hex(&self.from.0));
+        result.push_str("\",\"to\":\"0x");
+        result.push_str(&bytes_to_hex(&self.to.0));
+        result.push('"');
+        if self.kind != FrameKind::DelegateCall && self.kind != FrameKind::StaticCall {
+            result.push_str(&format!(",\"value\":\"0x{:x}\"", self.value));
+        }
+        result.push_str(&format!(
+            ",\"gas\":\"0x{:x}\",\"gasUsed\":\"0x{:x}\",\"input\":\"0x{}\"",
+            self.gas,
+            self.gas_used,
+            bytes_to_hex(&self.input)
+        ));
+        if !self.output.is_empty() {
+            result.push_str(",\"output\":\"0x");
+            result.push_str(&bytes_to_hex(&self.output));
+            result.push('"');
+        }
+        if let Some(error) = &self.error {
+            result.push_str(",\"error\":");
+            result.push_str(&json_string(error));
+        }
+        if let Some(reason) = &self.revert_reason {
+            result.push_str(",\"revertReason\":");
+            result.push_str(&json_string(reason));
+        }
+        if !self.calls.is_empty() {
+            result.push_str(",\"calls\":[");
+            for (i, call) in self.calls.iter().enumerate() {
+                if i > 0 {
+                    result.push(',');
+                }
+                result.push_str(&call.to_json());
+            }
+            result.push(']');
+        }
+        result.push('}');
+        result
+    }
+}
+
+/// Records the tree of frames of a transaction.
+#[derive(Default)]
+pub struct CallTracer {
+    /// Frames being executed, innermost last.
+    stack: Vec<CallFrame>,
+    root: Option<CallFrame>,
+    gas: U256,
+}
+
+impl CallTracer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The outermost frame, once it exited.
+    pub fn root(&self) -> Option<&CallFrame> {
+        self.root.as_ref()
+    }
+
+    pub fn into_root(self) -> Option<CallFrame> {
+        self.root
+    }
+}
+
+impl Tracer for CallTracer {
+    fn after_step(&mut self, step: &Step) {
+        self.gas = step.gas;
+    }
+
+    fn enter(&mut self, frame: &Frame) {
+        self.gas = frame.gas;
+        self.stack.push(CallFrame {
+            kind: frame.kind,
+            from: frame.from,
+            to: frame.to,
+            value: frame.value,
+            gas: frame.gas,
+            gas_used: U256::zero(),
+            input: frame.input.to_vec(),
+            output: Vec::new(),
+            error: None,
+            revert_reason: None,
+            calls: Vec::new(),
+        });
+    }
+
+    fn exit(&mut self, reason: &ExitReason, output: &[u8]) {
+        let mut frame = match self.stack.pop() {
+            Some(frame) => frame,
+            None => return,
+        };
+        frame.gas_used = frame.gas.saturating_sub(self.gas);
+        frame.output = output.to_vec();
+        frame.error = exit_error(reason);
+        if let ExitReason::Revert(_) = reason {
+            frame.revert_reason = decode_revert_reason(output);
+        }
+        match self.stack.last_mut() {
+            Some(parent) => parent.calls.push(frame),
+            None => self.root = Some(frame),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use super::*;
+    use crate::backend::{MemoryAccount, MemoryBackend, MemoryVicinity};
+    use crate::runner::Runner;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_call_tracer() {
+        let vicinity = MemoryVicinity::default();
+        let caller = H160::from_low_u64_be(1);
+        let callee = H160::from_low_u64_be(2);
+        let mut state = BTreeMap::new();
+        state.insert(
+            caller,
+            MemoryAccount {
+                // STATICCALL(gas, 0x02, 0, 0, 0, 0) then revert with its
+                // return data.
+                code: vec![
+                    0x60, 0x00, 0x80, 0x80, 0x80, 0x60, 0x02, 0x5a, 0xfa, 0x3d, 0x60, 0x00, 0x80,
+                    0x3e, 0x3d, 0x60, 0x00, 0xfd,
+                ],
+                ..Default::default()
+            },
+        );
+        let mut revert = vec![0x08, 0xc3, 0x79, 0xa0];
+        revert.resize(4 + 31, 0);
+        revert.push(0x20);
+        revert.resize(4 + 63, 0);
+        revert.push(2);
+        revert.extend_from_slice(b"no");
+        revert.resize(4 + 96, 0);
+        let mut code = Vec::new();
+        for (i, byte) in revert.iter().enumerate() {
+            // PUSH1 byte PUSH1 i MSTORE8
+            code.extend_from_slice(&[0x60, *byte, 0x60, i as u8, 0x53]);
+        }
+        // REVERT(0, 100)
+        code.extend_from_slice(&[0x60, revert.len() as u8, 0x60, 0x00, 0xfd]);
+        state.insert(
+            callee,
+            MemoryAccount {
+                code,
+                ..Default::default()
+            },
+        );
+        let mut backend = MemoryBackend::new(&vicinity, state);
+        let mut tracer = CallTracer::new();
+        Runner::execute(
+            &mut backend,
+            U256::zero(),
+            false,
+            false,
+            Some(&mut tracer),
+            |executor| executor.transact_call(H160::zero(), caller, U256::zero(), vec![0xab]),
+        );
+        let root = tracer.into_root().unwrap();
+
+        assert_eq!(root.kind, FrameKind::Call);
+        assert_eq!(root.to, caller);
+        assert_eq!(root.output, revert);
+        assert_eq!(root.revert_reason, Some("no".to_string()));
+        assert_eq!(root.calls.len(), 1);
+        assert_eq!(root.calls[0].kind, FrameKind::StaticCall);
+        assert_eq!(root.calls[0].from, caller);
+        assert_eq!(root.calls[0].to, callee);
+        assert_eq!(root.calls[0].error, Some("execution reverted".to_string()));
+        assert_eq!(
+            root.to_json(),
+            format!(
+                "{{\"type\":\"CALL\",\"from\":\"0x{}\",\"to\":\"0x{}\",\"value\":\"0x0\",\"gas\":\"0x0\",\"gasUsed\":\"0x0\",\"input\":\"0xab\",\"output\":\"0x{}\",\"error\":\"execution reverted\",\"revertReason\":\"no\",\"calls\":[{{\"type\":\"STATICCALL\",\"from\":\"0x{}\",\"to\":\"0x{}\",\"gas\":\"0x0\",\"gasUsed\":\"0x0\",\"input\":\"0x\",\"output\":\"0x{}\",\"error\":\"execution reverted\",\"revertReason\":\"no\"}}]}}",
+                bytes_to_hex(&H160::zero().0),
+                bytes_to_hex(&caller.0),
+                bytes_to_hex(&revert),
+                bytes_to_hex(&caller.0),
+                bytes_to_hex(&callee.0),
+                bytes_to_hex(&revert),
+            )
+        );
+    }
+}
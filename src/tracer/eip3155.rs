@@ -0,0 +1,210 @@
+//! EIP-3155 JSON traces, for diffing execution against other interpreters.
+//!
+//! `gasCost` and the summary's `gasUsed` are always zero (see the crate docs
+//! on gas), so leave gas fields out when diffing against geth or evmone.
+
+use std::io::{self, Write};
+use std::string::String;
+use std::vec::Vec;
+
+use primitive_types::{H256, U256};
+
+use super::{exit_error, json_string, ExitReason, Frame, Step, Tracer};
+use crate::evm_core::mnemonic;
+use crate::types::bytes_to_hex;
+
+/// Opcode traced before execution, written once its gas cost is known.
+struct PendingStep {
+    pc: usize,
+    opcode: u8,
+    gas: U256,
+    stack: Vec<H256>,
+    depth: usize,
+    memory_size: usize,
+}
+
+/// Writes an EIP-3155 JSON line for every executed opcode, followed by a
+/// summary line when the outermost frame exits.
+///
+/// The state root is not part of the summary, as it is only known once the
+/// state is committed.
+pub struct Eip3155Tracer<W: Write> {
+    writer: W,
+    pending: Option<PendingStep>,
+    /// Return data last returned to each frame being executed.
+    return_data: Vec<Vec<u8>>,
+    start_gas: U256,
+    gas: U256,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> Eip3155Tracer<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            pending: None,
+            return_data: Vec::new(),
+            start_gas: U256::zero(),
+            gas: U256::zero(),
+            error: None,
+        }
+    }
+
+    /// Returns the writer, or the first error writing to it.
+    pub fn into_inner(self) -> io::Result<W> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(self.writer),
+        }
+    }
+
+    fn write_line(&mut self, line: &str) {
+        if self.error.is_none() {
+            if let Err(error) = writeln!(self.writer, "{}", line) {
+                self.error = Some(error);
+            }
+        }
+    }
+
+    /// Writes the pending step, with gas left after it if it already ran.
+    fn flush(&mut self, gas_after: Option<U256>) {
+        let step = match self.pending.take() {
+            Some(step) => step,
+            None => return,
+        };
+        let gas_cost = gas_after
+            .map(|gas| step.gas.saturating_sub(gas))
+            .unwrap_or_default();
+        let stack: Vec<String> = step
+            .stack
+            .iter()
+            .map(|value| format!("\"0x{:x}\"", U256::from_big_endian(value.as_bytes())))
+            .collect();
+        let return_data = self
+            .return_data
+            .last()
+            .map(|data| bytes_to_hex(data))
+            .unwrap_or_default();
+        let mut line = format!(
+            "{{\"pc\":{},\"op\":{},\"gas\":\"0x{:x}\",\"gasCost\":\"0x{:x}\",\"memSize\":{},\"stack\":[{}],\"depth\":{},\"returnData\":\"0x{}\",\"refund\":0",
+            step.pc,
+            step.opcode,
+            step.gas,
+            gas_cost,
+            step.memory_size,
+            stack.join(","),
+            step.depth + 1,
+            return_data,
+        );
+        if let Some(name) = mnemonic(step.opcode) {
+            line.push_str(&format!(",\"opName\":\"{}\"", name));
+        }
+        line.push('}');
+        self.write_line(&line);
+    }
+}
+
+impl<W: Write> Tracer for Eip3155Tracer<W> {
+    fn before_step(&mut self, step: &Step) {
+        self.flush(None);
+        self.gas = step.gas;
+        self.pending = Some(PendingStep {
+            pc: step.pc,
+            opcode: step.opcode,
+            gas: step.gas,
+            stack: step.stack.to_vec(),
+            depth: step.depth,
+            memory_size: step.memory_size,
+        });
+    }
+
+    fn after_step(&mut self, step: &Step) {
+        let is_pending = self.pending.as_ref().map_or(false, |pending| {
+            pending.depth == step.depth && pending.pc == step.pc
+        });
+        if is_pending {
+            self.flush(Some(step.gas));
+        }
+        self.gas = step.gas;
+    }
+
+    fn enter(&mut self, frame: &Frame) {
+        // Steps of the calling opcode are written before the callee's.
+        self.flush(None);
+        if self.return_data.is_empty() {
+            self.start_gas = frame.gas;
+            self.gas = frame.gas;
+        }
+        self.return_data.push(Vec::new());
+    }
+
+    fn exit(&mut self, reason: &ExitReason, output: &[u8]) {
+        self.flush(None);
+        self.return_data.pop();
+        if let Some(return_data) = self.return_data.last_mut() {
+            *return_data = output.to_vec();
+            return;
+        }
+        let mut line = format!(
+            "{{\"output\":\"0x{}\",\"gasUsed\":\"0x{:x}\",\"pass\":{}",
+            bytes_to_hex(output),
+            self.start_gas.saturating_sub(self.gas),
+            reason.is_succeed(),
+        );
+        if let Some(error) = exit_error(reason) {
+            line.push_str(",\"error\":");
+            line.push_str(&json_string(&error));
+        }
+        line.push('}');
+        self.write_line(&line);
+    }
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use super::*;
+    use crate::backend::{MemoryAccount, MemoryBackend, MemoryVicinity};
+    use crate::runner::Runner;
+    use primitive_types::H160;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_eip3155_tracer() {
+        let vicinity = MemoryVicinity::default();
+        let address = H160::from_low_u64_be(1);
+        let mut state = BTreeMap::new();
+        state.insert(
+            address,
+            MemoryAccount {
+                // PUSH1 0x2a PUSH1 0x00 MSTORE PUSH1 0x20 PUSH1 0x00 RETURN
+                code: vec![0x60, 0x2a, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3],
+                ..Default::default()
+            },
+        );
+        let mut backend = MemoryBackend::new(&vicinity, state);
+        let mut tracer = Eip3155Tracer::new(Vec::new());
+        Runner::execute(
+            &mut backend,
+            U256::zero(),
+            false,
+            false,
+            Some(&mut tracer),
+            |executor| executor.transact_call(H160::zero(), address, U256::zero(), Vec::new()),
+        );
+        let output = String::from_utf8(tracer.into_inner().unwrap()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert_eq!(lines.len(), 7);
+        assert_eq!(
+            lines[2],
+            "{\"pc\":4,\"op\":82,\"gas\":\"0x0\",\"gasCost\":\"0x0\",\"memSize\":0,\"stack\":[\"0x2a\",\"0x0\"],\"depth\":1,\"returnData\":\"0x\",\"refund\":0,\"opName\":\"MSTORE\"}"
+        );
+        assert_eq!(
+            lines[6],
+            format!(
+                "{{\"output\":\"0x{:064x}\",\"gasUsed\":\"0x0\",\"pass\":true}}",
+                0x2a
+            )
+        );
+    }
+}
@@ -0,0 +1,242 @@
+//! Hooks for observing execution opcode by opcode.
+//!
+//! A `Tracer` is passed to `Runner::execute`, or to the `Runner`'s
+//! `*_with_tracer` functions. Executors only report to tracers with the
+//! `tracing` feature, which keeps the hooks out of the contract build.
+//!
+//! The gas reported to tracers is the backend's gas left, see the crate docs
+//! on gas.
+
+#[cfg(not(feature = "std"))]
+use alloc::{format, string::String};
+#[cfg(feature = "std")]
+use std::{format, string::String};
+
+use primitive_types::{H160, H256, U256};
+
+pub use crate::evm_core::{ExitError, ExitFatal, ExitReason, ExitRevert, ExitSucceed};
+
+mod call;
+#[cfg(feature = "std")]
+mod eip3155;
+
+pub use self::call::{CallFrame, CallTracer};
+#[cfg(feature = "std")]
+pub use self::eip3155::Eip3155Tracer;
+
+/// Interpreter state around a single opcode.
+#[derive(Clone, Copy, Debug)]
+pub struct Step<'a> {
+    /// Call depth, 0 for the frame of the transaction itself.
+    pub depth: usize,
+    /// Program counter of the opcode.
+    pub pc: usize,
+    pub opcode: u8,
+    /// Stack, with the top of the stack last.
+    pub stack: &'a [H256],
+    /// Memory size in bytes.
+    pub memory_size: usize,
+    /// Gas left according to the backend.
+    pub gas: U256,
+}
+
+/// Kind of a call frame.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FrameKind {
+    Call,
+    CallCode,
+    DelegateCall,
+    StaticCall,
+    Create,
+    Create2,
+}
+
+/// A call or create frame being entered.
+#[derive(Clone, Copy, Debug)]
+pub struct Frame<'a> {
+    pub kind: FrameKind,
+    /// Call depth the frame runs at.
+    pub depth: usize,
+    /// Account making the call.
+    pub from: H160,
+    /// Account whose code runs, or the address being created.
+    pub to: H160,
+    pub value: U256,
+    /// Call data, or the init code of a create.
+    pub input: &'a [u8],
+    /// Gas left according to the backend.
+    pub gas: U256,
+}
+
+/// Receives execution events. All methods do nothing by default.
+///
+/// `before_step` and `after_step` are called around every opcode of every
+/// frame; the stack and memory size in `after_step` are those left by the
+/// opcode. Frames are reported through `enter` and `exit`, which nest within
+/// the steps of the calling opcode.
+pub trait Tracer {
+    fn before_step(&mut self, _step: &Step) {}
+    fn after_step(&mut self, _step: &Step) {}
+    fn enter(&mut self, _frame: &Frame) {}
+    /// Frame exit with its return data, or the deployed code of a successful
+    /// create.
+    fn exit(&mut self, _reason: &ExitReason, _output: &[u8]) {}
+    fn sload(&mut self, _address: H160, _index: H256, _value: H256) {}
+    fn sstore(&mut self, _address: H160, _index: H256, _value: H256) {}
+}
+
+/// Error message of an unsuccessful exit.
+pub(crate) fn exit_error(reason: &ExitReason) -> Option<String> {
+    match reason {
+        ExitReason::Succeed(_) => None,
+        ExitReason::Revert(_) => Some(String::from("execution reverted")),
+        ExitReason::Error(error) => Some(String::from(&error.message()[..])),
+        ExitReason::Fatal(fatal) => Some(String::from(&fatal.message()[..])),
+    }
+}
+
+/// `value` as a quoted JSON string.
+pub(crate) fn json_string(value: &str) -> String {
+    let mut result = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use super::*;
+    use crate::backend::{MemoryAccount, MemoryBackend, MemoryVicinity};
+    use crate::runner::Runner;
+    use std::collections::BTreeMap;
+
+    #[derive(Default)]
+    struct Recorder {
+        steps: Vec<(usize, u8, usize, bool)>,
+        frames: Vec<(FrameKind, usize, H160)>,
+        exits: Vec<ExitReason>,
+        storage: Vec<(bool, H256, H256)>,
+    }
+
+    impl Tracer for Recorder {
+        fn before_step(&mut self, step: &Step) {
+            self.steps
+                .push((step.pc, step.opcode, step.stack.len(), false));
+        }
+        fn after_step(&mut self, step: &Step) {
+            self.steps
+                .push((step.pc, step.opcode, step.stack.len(), true));
+        }
+        fn enter(&mut self, frame: &Frame) {
+            self.frames.push((frame.kind, frame.depth, frame.to));
+        }
+        fn exit(&mut self, reason: &ExitReason, _output: &[u8]) {
+            self.exits.push(reason.clone());
+        }
+        fn sload(&mut self, _address: H160, index: H256, value: H256) {
+            self.storage.push((false, index, value));
+        }
+        fn sstore(&mut self, _address: H160, index: H256, value: H256) {
+            self.storage.push((true, index, value));
+        }
+    }
+
+    #[test]
+    fn test_tracer() {
+        let vicinity = MemoryVicinity::default();
+        let address = H160::from_low_u64_be(1);
+        let mut state = BTreeMap::new();
+        state.insert(
+            address,
+            MemoryAccount {
+                // PUSH1 0x2a PUSH1 0x01 SSTORE PUSH1 0x01 SLOAD STOP
+                code: vec![0x60, 0x2a, 0x60, 0x01, 0x55, 0x60, 0x01, 0x54, 0x00],
+                ..Default::default()
+            },
+        );
+        let mut backend = MemoryBackend::new(&vicinity, state);
+        let mut tracer = Recorder::default();
+        let reason = Runner::execute(
+            &mut backend,
+            U256::zero(),
+            false,
+            false,
+            Some(&mut tracer),
+            |executor| executor.transact_call(H160::zero(), address, U256::zero(), Vec::new()),
+        )
+        .reason;
+        assert!(reason.is_succeed());
+
+        assert_eq!(tracer.frames, vec![(FrameKind::Call, 0, address)]);
+        assert_eq!(tracer.exits, vec![reason]);
+        let slot = H256::from_low_u64_be(1);
+        let value = H256::from_low_u64_be(0x2a);
+        assert_eq!(
+            tracer.storage,
+            vec![(true, slot, value), (false, slot, value)]
+        );
+        assert_eq!(
+            tracer.steps,
+            vec![
+                (0, 0x60, 0, false),
+                (0, 0x60, 1, true),
+                (2, 0x60, 1, false),
+                (2, 0x60, 2, true),
+                (4, 0x55, 2, false),
+                (4, 0x55, 0, true),
+                (5, 0x60, 0, false),
+                (5, 0x60, 1, true),
+                (7, 0x54, 1, false),
+                (7, 0x54, 1, true),
+                (8, 0x00, 1, false),
+                (8, 0x00, 1, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_callcode_to_self() {
+        let vicinity = MemoryVicinity::default();
+        let address = H160::from_low_u64_be(1);
+        let mut state = BTreeMap::new();
+        state.insert(
+            address,
+            MemoryAccount {
+                // Without input: CALLCODE(GAS, ADDRESS, 0, 0, 1, 0, 0) POP
+                code: vec![
+                    0x36, 0x60, 0x12, 0x57, 0x60, 0x00, 0x60, 0x00, 0x60, 0x01, 0x60, 0x00, 0x60,
+                    0x00, 0x30, 0x5a, 0xf2, 0x50, 0x5b, 0x00,
+                ],
+                ..Default::default()
+            },
+        );
+        let mut backend = MemoryBackend::new(&vicinity, state);
+        let mut tracer = Recorder::default();
+        let reason = Runner::execute(
+            &mut backend,
+            U256::zero(),
+            false,
+            false,
+            Some(&mut tracer),
+            |executor| executor.transact_call(H160::zero(), address, U256::zero(), Vec::new()),
+        )
+        .reason;
+        assert!(reason.is_succeed());
+
+        assert_eq!(
+            tracer.frames,
+            vec![
+                (FrameKind::Call, 0, address),
+                (FrameKind::CallCode, 1, address)
+            ]
+        );
+    }
+}
@@ -0,0 +1,614 @@
+//! # Merkle Patricia Trie
+//!
+//! Ethereum compatible trie roots of state and storage, and EIP-1186 proofs.
+//! Roots and proofs are computed from scratch over all items, sorted by key.
+//!
+//! Only in-memory state has a root, see `MemoryBackend::state_root`. The NEAR
+//! storage keeps no trie nodes, so it has no state root: one would need the
+//! nodes stored and updated on every write.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::vec::Vec;
+
+use primitive_types::{H256, U256};
+use rlp::{Rlp, RlpStream};
+
+use crate::types::{keccak, u256_to_arr, AccountProof, StorageProof};
+
+/// Root of an empty trie: `keccak(rlp(""))`.
+pub const EMPTY_TRIE_ROOT: H256 = H256([
+    0x56, 0xe8, 0x1f, 0x17, 0x1b, 0xcc, 0x55, 0xa6, 0xff, 0x83, 0x45, 0xe6, 0x92, 0xc0, 0xf8, 0x6e,
+    0x5b, 0x48, 0xe0, 0x1b, 0x99, 0x6c, 0xad, 0xc0, 0x01, 0x62, 0x2f, 0xb5, 0xe3, 0x63, 0xb4, 0x21,
+]);
+
+/// Root of the trie containing `items`. Keys must be unique.
+pub fn trie_root<I, K, V>(items: I) -> H256
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>,
+{
+    keccak(&encode_node(&sorted_items(items), 0, None, &mut Vec::new()))
+}
+
+/// Proof of `key` in the trie containing `items`: all nodes referenced by
+/// hash on the path to the key, starting from the root. Proves absence if
+/// the key is not in the trie.
+pub fn trie_proof<I, K, V>(items: I, key: &[u8]) -> Vec<Vec<u8>>
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>,
+{
+    let items = sorted_items(items);
+    if items.is_empty() {
+        return Vec::new();
+    }
+    let mut proof = Vec::new();
+    encode_node(&items, 0, Some(&to_nibbles(key)), &mut proof);
+    proof.reverse();
+    proof
+}
+
+/// Root of the secure trie containing `items`, where keys are hashed with
+/// `keccak`, as used for Ethereum state and storage tries.
+pub fn sec_trie_root<I, K, V>(items: I) -> H256
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>,
+{
+    trie_root(
+        items
+            .into_iter()
+            .map(|(key, value)| (keccak(key.as_ref()), value)),
+    )
+}
+
+/// Proof of `key` in the secure trie containing `items`.
+pub fn sec_trie_proof<I, K, V>(items: I, key: &[u8]) -> Vec<Vec<u8>>
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>,
+{
+    trie_proof(
+        items
+            .into_iter()
+            .map(|(key, value)| (keccak(key.as_ref()), value)),
+        keccak(key).as_bytes(),
+    )
+}
+
+/// Root of the storage trie of an account. Zero values are skipped, as
+/// they are not stored.
+pub fn storage_root<'a, I>(storage: I) -> H256
+where
+    I: IntoIterator<Item = (&'a H256, &'a H256)>,
+{
+    sec_trie_root(storage_items(storage))
+}
+
+/// Proofs of storage slots `keys` of an account.
+pub fn storage_proofs<'a, I>(storage: I, keys: &[H256]) -> Vec<StorageProof>
+where
+    I: IntoIterator<Item = (&'a H256, &'a H256)>,
+{
+    let items = storage_items(storage);
+    keys.iter()
+        .map(|key| {
+            let value = items
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, value)| rlp::decode(value).unwrap())
+                .unwrap_or_else(U256::zero);
+            StorageProof {
+                key: key.0,
+                value: u256_to_arr(&value),
+                proof: sec_trie_proof(items.iter().cloned(), key.as_bytes()),
+            }
+        })
+        .collect()
+}
+
+/// Non-zero storage slots with RLP encoded values, as stored in the trie.
+fn storage_items<'a, I>(storage: I) -> Vec<(H256, Vec<u8>)>
+where
+    I: IntoIterator<Item = (&'a H256, &'a H256)>,
+{
+    storage
+        .into_iter()
+        .filter(|(_, value)| **value != H256::zero())
+        .map(|(key, value)| {
+            (
+                *key,
+                rlp::encode(&U256::from_big_endian(value.as_bytes())).to_vec(),
+            )
+        })
+        .collect()
+}
+
+/// RLP encoding of an account in the state trie.
+pub fn account_rlp(nonce: &U256, balance: &U256, storage_root: &H256, code_hash: &H256) -> Vec<u8> {
+    let mut stream = RlpStream::new_list(4);
+    stream.append(nonce);
+    stream.append(balance);
+    stream.append(storage_root);
+    stream.append(code_hash);
+    stream.out().to_vec()
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum ProofError {
+    /// A node referenced by hash is not in the proof.
+    MissingNode,
+    /// A node doesn't match its hash or is not a valid trie node.
+    InvalidNode,
+    /// The proven account doesn't match the proof.
+    InvalidAccount,
+    /// A proven storage value doesn't match the proof.
+    InvalidStorage,
+}
+
+impl From<rlp::DecoderError> for ProofError {
+    fn from(_: rlp::DecoderError) -> Self {
+        ProofError::InvalidNode
+    }
+}
+
+/// Verifies the proof of `key` in the trie with `root`. Returns the value of
+/// the key, or `None` if the proof shows it is not in the trie.
+pub fn verify_proof(
+    root: &H256,
+    key: &[u8],
+    proof: &[Vec<u8>],
+) -> Result<Option<Vec<u8>>, ProofError> {
+    if *root == EMPTY_TRIE_ROOT {
+        return Ok(None);
+    }
+    let key = to_nibbles(key);
+    let mut path = &key[..];
+    let mut nodes = proof.iter();
+    let mut hash = *root;
+    let mut node = next_node(&mut nodes, &hash)?;
+    loop {
+        let rlp = Rlp::new(&node);
+        let child = match rlp.item_count()? {
+            2 => {
+                let (partial, leaf) = from_hex_prefix(rlp.at(0)?.data()?)?;
+                if leaf {
+                    if path == &partial[..] {
+                        return Ok(Some(rlp.at(1)?.data()?.to_vec()));
+                    }
+                    return Ok(None);
+                }
+                if !path.starts_with(&partial) {
+                    return Ok(None);
+                }
+                path = &path[partial.len()..];
+                rlp.at(1)?
+            }
+            17 => {
+                if path.is_empty() {
+                    let value = rlp.at(16)?.data()?;
+                    if value.is_empty() {
+                        return Ok(None);
+                    }
+                    return Ok(Some(value.to_vec()));
+                }
+                let child = rlp.at(path[0] as usize)?;
+                path = &path[1..];
+                child
+            }
+            _ => return Err(ProofError::InvalidNode),
+        };
+        node = if child.is_list() {
+            // Embedded node.
+            child.as_raw().to_vec()
+        } else {
+            let data = child.data()?;
+            if data.is_empty() {
+                return Ok(None);
+            }
+            if data.len() != 32 {
+                return Err(ProofError::InvalidNode);
+            }
+            hash = H256::from_slice(data);
+            next_node(&mut nodes, &hash)?
+        };
+    }
+}
+
+/// Verifies an EIP-1186 account proof, including all its storage proofs,
+/// against `state_root`.
+pub fn verify_account_proof(state_root: &H256, proof: &AccountProof) -> Result<(), ProofError> {
+    let nonce = U256::from_big_endian(&proof.nonce);
+    let balance = U256::from_big_endian(&proof.balance);
+    let storage_hash = H256(proof.storage_hash);
+    let code_hash = H256(proof.code_hash);
+    let expected = match verify_proof(
+        state_root,
+        keccak(&proof.address).as_bytes(),
+        &proof.account_proof,
+    )? {
+        Some(account) => account,
+        None => account_rlp(&U256::zero(), &U256::zero(), &EMPTY_TRIE_ROOT, &keccak(&[])),
+    };
+    if account_rlp(&nonce, &balance, &storage_hash, &code_hash) != expected {
+        return Err(ProofError::InvalidAccount);
+    }
+    for storage_proof in proof.storage_proof.iter() {
+        let value = match verify_proof(
+            &storage_hash,
+            keccak(&storage_proof.key).as_bytes(),
+            &storage_proof.proof,
+        )? {
+            Some(value) => rlp::decode(&value)?,
+            None => U256::zero(),
+        };
+        if value != U256::from_big_endian(&storage_proof.value) {
+            return Err(ProofError::InvalidStorage);
+        }
+    }
+    Ok(())
+}
+
+fn next_node<'a, I>(nodes: &mut I, hash: &H256) -> Result<Vec<u8>, ProofError>
+where
+    I: Iterator<Item = &'a Vec<u8>>,
+{
+    let node = nodes.next().ok_or(ProofError::MissingNode)?;
+    if keccak(node) != *hash {
+        return Err(ProofError::InvalidNode);
+    }
+    Ok(node.clone())
+}
+
+fn sorted_items<I, K, V>(items: I) -> Vec<(Vec<u8>, Vec<u8>)>
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>,
+{
+    let mut items: Vec<(Vec<u8>, Vec<u8>)> = items
+        .into_iter()
+        .map(|(key, value)| (to_nibbles(key.as_ref()), value.as_ref().to_vec()))
+        .collect();
+    items.sort();
+    items
+}
+
+fn to_nibbles(key: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(key.len() * 2);
+    for byte in key {
+        result.push(byte >> 4);
+        result.push(byte & 0xf);
+    }
+    result
+}
+
+/// Hex-prefix encoding of a path, flagging whether it ends in a leaf.
+fn hex_prefix(nibbles: &[u8], leaf: bool) -> Vec<u8> {
+    let flag = if leaf { 0x20 } else { 0x00 };
+    let mut result = Vec::with_capacity(nibbles.len() / 2 + 1);
+    let rest = if nibbles.len() % 2 == 1 {
+        result.push(flag | 0x10 | nibbles[0]);
+        &nibbles[1..]
+    } else {
+        result.push(flag);
+        nibbles
+    };
+    for pair in rest.chunks(2) {
+        result.push((pair[0] << 4) | pair[1]);
+    }
+    result
+}
+
+/// Decodes a hex-prefix encoded path, returning whether it ends in a leaf.
+fn from_hex_prefix(encoded: &[u8]) -> Result<(Vec<u8>, bool), ProofError> {
+    if encoded.is_empty() || encoded[0] >> 4 > 3 {
+        return Err(ProofError::InvalidNode);
+    }
+    let leaf = encoded[0] & 0x20 != 0;
+    let mut nibbles = to_nibbles(encoded);
+    // Drop the flag, and the padding nibble for even paths.
+    let skip = if encoded[0] & 0x10 != 0 { 1 } else { 2 };
+    nibbles.drain(..skip);
+    Ok((nibbles, leaf))
+}
+
+/// RLP encoding of the node holding sorted `items`, which share their first
+/// `depth` nibbles. If `path` is given, nodes on the path that are referenced
+/// by hash are added to `proof`, children first.
+fn encode_node(
+    items: &[(Vec<u8>, Vec<u8>)],
+    depth: usize,
+    path: Option<&[u8]>,
+    proof: &mut Vec<Vec<u8>>,
+) -> Vec<u8> {
+    let node = encode_node_inner(items, depth, path, proof);
+    // Only the root is at depth zero.
+    if path.is_some() && (node.len() >= 32 || depth == 0) {
+        proof.push(node.clone());
+    }
+    node
+}
+
+fn encode_node_inner(
+    items: &[(Vec<u8>, Vec<u8>)],
+    depth: usize,
+    path: Option<&[u8]>,
+    proof: &mut Vec<Vec<u8>>,
+) -> Vec<u8> {
+    if items.is_empty() {
+        return rlp::NULL_RLP.to_vec();
+    }
+    if items.len() == 1 {
+        let (key, value) = &items[0];
+        let mut stream = RlpStream::new_list(2);
+        stream.append(&hex_prefix(&key[depth..], true));
+        stream.append(value);
+        return stream.out().to_vec();
+    }
+
+    // Items are sorted, so the first and last share the common prefix.
+    let first = &items[0].0;
+    let last = &items[items.len() - 1].0;
+    let shared = first[depth..]
+        .iter()
+        .zip(last[depth..].iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    if shared > 0 {
+        let prefix = &first[depth..depth + shared];
+        let path =
+            path.filter(|path| path.len() >= depth + shared && path[depth..].starts_with(prefix));
+        let mut stream = RlpStream::new_list(2);
+        stream.append(&hex_prefix(prefix, false));
+        append_child(
+            &mut stream,
+            &encode_node(items, depth + shared, path, proof),
+        );
+        return stream.out().to_vec();
+    }
+
+    let mut stream = RlpStream::new_list(17);
+    let mut rest = items;
+    let mut value = None;
+    // A key ending here sorts first and is the value of the branch.
+    if rest[0].0.len() == depth {
+        value = Some(&rest[0].1);
+        rest = &rest[1..];
+    }
+    for nibble in 0..16 {
+        let len = rest
+            .iter()
+            .take_while(|(key, _)| key[depth] == nibble)
+            .count();
+        if len == 0 {
+            stream.append_empty_data();
+        } else {
+            let path = path.filter(|path| path.len() > depth && path[depth] == nibble);
+            append_child(
+                &mut stream,
+                &encode_node(&rest[..len], depth + 1, path, proof),
+            );
+        }
+        rest = &rest[len..];
+    }
+    match value {
+        Some(value) => stream.append(value),
+        None => stream.append_empty_data(),
+    };
+    stream.out().to_vec()
+}
+
+/// Nodes shorter than a hash are embedded in their parent.
+fn append_child(stream: &mut RlpStream, node: &[u8]) {
+    if node.len() < 32 {
+        stream.append_raw(node, 1);
+    } else {
+        stream.append(&keccak(node));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trie_root() {
+        let empty: Vec<(Vec<u8>, Vec<u8>)> = Vec::new();
+        assert_eq!(trie_root(empty), EMPTY_TRIE_ROOT);
+        assert_eq!(
+            trie_root(vec![
+                ("doe", "reindeer"),
+                ("dog", "puppy"),
+                ("dogglesworth", "cat"),
+            ]),
+            H256::from_slice(
+                &hex::decode("8aad789dff2f538bca5d8ea56e8abe10f4c7ba3a5dea95fea4cd6e7c3a1168d3")
+                    .unwrap()
+            )
+        );
+        assert_eq!(
+            trie_root(vec![
+                ("do", "verb"),
+                ("horse", "stallion"),
+                ("doge", "coin"),
+                ("dog", "puppy"),
+            ]),
+            H256::from_slice(
+                &hex::decode("5991bb8c6514148a29db676a14ac506cd2cd5775ace63c30a4fe457715e9ac84")
+                    .unwrap()
+            )
+        );
+    }
+
+    fn h256(hex: &str) -> H256 {
+        H256::from_slice(&hex::decode(hex).unwrap())
+    }
+
+    #[test]
+    fn test_storage_root() {
+        let empty: Vec<(&H256, &H256)> = Vec::new();
+        assert_eq!(storage_root(empty), EMPTY_TRIE_ROOT);
+        // Storage of a contract that set slot 0 to 1, as in ethereum/tests.
+        let storage = vec![
+            (H256::zero(), H256::from_low_u64_be(1)),
+            (H256::from_low_u64_be(1), H256::zero()),
+        ];
+        assert_eq!(
+            storage_root(storage.iter().map(|(key, value)| (key, value))),
+            h256("821e2556a290c86405f8160a2d662042a431ba456b9db265c79bb837c04be5f0")
+        );
+    }
+
+    /// Accounts of `test1` in ethereum/tests
+    /// TrieTests/hex_encoded_securetrie_test.json, by address.
+    const SECURETRIE_TEST1: [(&str, &str); 5] = [
+        (
+            "a94f5374fce5edbc8e2a8697c15331677e6ebf0b",
+            "f848018405f446a7a056e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421a0c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470",
+        ),
+        (
+            "095e7baea6a6c7c4c2dfeb977efac326af552d87",
+            "f8440101a056e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421a004bccc5d94f4d1f99aab44369a910179931772f2a5c001c3229f57831c102769",
+        ),
+        (
+            "d2571607e241ecf590ed94b12d87c94babe36db6",
+            "f8440180a0ba4b47865c55a341a4a78759bb913cd15c3ee8eaf30a62fa8d1c8863113d84e8a0c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470",
+        ),
+        (
+            "62c01474f089b07dae603491675dc5b5748f7049",
+            "f8448080a056e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421a0c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470",
+        ),
+        (
+            "2adc25665018aa1fe0e6bc666dac8fc2697ff9ba",
+            "f8478083019a59a056e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421a0c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470",
+        ),
+    ];
+
+    const SECURETRIE_TEST1_ROOT: &str =
+        "730a444e08ab4b8dee147c9b232fc52d34a223d600031c1e9d25bfc985cbd797";
+
+    fn securetrie_test1() -> Vec<(Vec<u8>, Vec<u8>)> {
+        SECURETRIE_TEST1
+            .iter()
+            .map(|(address, account)| {
+                (hex::decode(address).unwrap(), hex::decode(account).unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_state_root() {
+        let empty_code = keccak(&[]);
+        let encoded = [
+            account_rlp(&1.into(), &0x05f446a7.into(), &EMPTY_TRIE_ROOT, &empty_code),
+            account_rlp(
+                &1.into(),
+                &1.into(),
+                &EMPTY_TRIE_ROOT,
+                &h256("04bccc5d94f4d1f99aab44369a910179931772f2a5c001c3229f57831c102769"),
+            ),
+            account_rlp(
+                &1.into(),
+                &0.into(),
+                &h256("ba4b47865c55a341a4a78759bb913cd15c3ee8eaf30a62fa8d1c8863113d84e8"),
+                &empty_code,
+            ),
+            account_rlp(&0.into(), &0.into(), &EMPTY_TRIE_ROOT, &empty_code),
+            account_rlp(&0.into(), &0x019a59.into(), &EMPTY_TRIE_ROOT, &empty_code),
+        ];
+        for (encoded, (_, expected)) in encoded.iter().zip(SECURETRIE_TEST1.iter()) {
+            assert_eq!(hex::encode(encoded), *expected);
+        }
+        assert_eq!(
+            sec_trie_root(securetrie_test1()),
+            h256(SECURETRIE_TEST1_ROOT)
+        );
+    }
+
+    #[test]
+    fn test_verify_account_proof() {
+        let accounts = securetrie_test1();
+        let root = h256(SECURETRIE_TEST1_ROOT);
+        for (address, encoded) in accounts.iter() {
+            let rlp = Rlp::new(encoded);
+            let mut proof = AccountProof {
+                address: [0u8; 20],
+                nonce: u256_to_arr(&rlp.val_at(0).unwrap()),
+                balance: u256_to_arr(&rlp.val_at(1).unwrap()),
+                storage_hash: rlp.val_at::<H256>(2).unwrap().0,
+                code_hash: rlp.val_at::<H256>(3).unwrap().0,
+                account_proof: sec_trie_proof(accounts.clone(), address),
+                storage_proof: Vec::new(),
+            };
+            proof.address.copy_from_slice(address);
+            assert_eq!(verify_account_proof(&root, &proof), Ok(()));
+
+            let mut forged = proof.clone();
+            forged.balance = u256_to_arr(&U256::from(1_000_000));
+            assert_eq!(
+                verify_account_proof(&root, &forged),
+                Err(ProofError::InvalidAccount)
+            );
+        }
+
+        // An absent account is proven empty.
+        let address = [0x11u8; 20];
+        let mut proof = AccountProof {
+            address,
+            nonce: [0u8; 32],
+            balance: [0u8; 32],
+            storage_hash: EMPTY_TRIE_ROOT.0,
+            code_hash: keccak(&[]).0,
+            account_proof: sec_trie_proof(accounts.clone(), &address),
+            storage_proof: vec![StorageProof {
+                key: [0u8; 32],
+                value: [0u8; 32],
+                proof: Vec::new(),
+            }],
+        };
+        assert_eq!(verify_account_proof(&root, &proof), Ok(()));
+        proof.nonce = u256_to_arr(&U256::one());
+        assert_eq!(
+            verify_account_proof(&root, &proof),
+            Err(ProofError::InvalidAccount)
+        );
+    }
+
+    #[test]
+    fn test_trie_proof() {
+        let items = vec![
+            ("do", "verb"),
+            ("horse", "stallion"),
+            ("doge", "coin"),
+            ("dog", "puppy"),
+        ];
+        let root = trie_root(items.clone());
+        for (key, value) in items.iter() {
+            let proof = trie_proof(items.clone(), key.as_bytes());
+            assert_eq!(
+                verify_proof(&root, key.as_bytes(), &proof),
+                Ok(Some(value.as_bytes().to_vec()))
+            );
+        }
+        for key in ["d", "dogs", "cat", "horses"].iter() {
+            let proof = trie_proof(items.clone(), key.as_bytes());
+            assert_eq!(verify_proof(&root, key.as_bytes(), &proof), Ok(None));
+        }
+        let proof = trie_proof(items.clone(), b"dog");
+        assert_eq!(
+            verify_proof(&root, b"dog", &proof[..proof.len() - 1]),
+            Err(ProofError::MissingNode)
+        );
+        assert_eq!(
+            verify_proof(&root, b"dog", &proof[1..]),
+            Err(ProofError::InvalidNode)
+        );
+    }
+}
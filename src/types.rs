@@ -1,7 +1,7 @@
 #[cfg(not(feature = "std"))]
-use alloc::{string::String, vec, vec::Vec};
+use alloc::{format, string::String, vec, vec::Vec};
 #[cfg(feature = "std")]
-use std::{string::String, vec, vec::Vec};
+use std::{format, string::String, vec, vec::Vec};
 
 #[cfg(not(feature = "contract"))]
 use sha3::{Digest, Keccak256};
@@ -10,6 +10,9 @@ use borsh::{BorshDeserialize, BorshSerialize};
 use primitive_types::{H160, H256, U256};
 
 use crate::backend::Log;
+use crate::evm_core::mnemonic;
+pub use crate::evm_core::{ExitError, ExitFatal, ExitReason, ExitRevert, ExitSucceed};
+use crate::stack::{AccountDiff, ErrorLocation, StateDiff};
 
 #[cfg(feature = "contract")]
 use crate::sdk;
@@ -38,11 +41,425 @@ pub struct GetStorageAtArgs {
     pub key: RawH256,
 }
 
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct CleanupStorageArgs {
+    pub address: RawAddress,
+    /// Maximum number of slots to remove.
+    pub limit: u64,
+}
+
+/// Log emitted during a transaction.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Eq, PartialEq)]
+pub struct ResultLog {
+    pub address: RawAddress,
+    pub topics: Vec<RawH256>,
+    pub data: Vec<u8>,
+}
+
+impl From<Log> for ResultLog {
+    fn from(log: Log) -> Self {
+        Self {
+            address: log.address.0,
+            topics: log.topics.into_iter().map(|topic| topic.0).collect(),
+            data: log.data,
+        }
+    }
+}
+
+/// Changes to an account made by a transaction, with previous and new
+/// values.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Eq, PartialEq)]
+pub struct ResultAccountDiff {
+    pub address: RawAddress,
+    pub nonce: (RawU256, RawU256),
+    pub balance: (RawU256, RawU256),
+    /// Only set if the code changed.
+    pub code: Option<(Vec<u8>, Vec<u8>)>,
+    /// Written storage slots whose value changed, by key.
+    pub storage: Vec<(RawH256, RawH256, RawH256)>,
+    pub reset_storage: bool,
+    pub deleted: bool,
+}
+
+impl From<(H160, AccountDiff)> for ResultAccountDiff {
+    fn from((address, diff): (H160, AccountDiff)) -> Self {
+        let code = if diff.code.from != diff.code.to {
+            Some((diff.code.from, diff.code.to))
+        } else {
+            None
+        };
+        Self {
+            address: address.0,
+            nonce: (u256_to_arr(&diff.nonce.from), u256_to_arr(&diff.nonce.to)),
+            balance: (
+                u256_to_arr(&diff.balance.from),
+                u256_to_arr(&diff.balance.to),
+            ),
+            code,
+            storage: diff
+                .storage
+                .into_iter()
+                .map(|(key, change)| (key.0, change.from.0, change.to.0))
+                .collect(),
+            reset_storage: diff.reset_storage,
+            deleted: diff.deleted,
+        }
+    }
+}
+
+/// Outcome of a view call with the changes it would make to the state.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Eq, PartialEq)]
+pub struct StateDiffResult {
+    pub status: TransactionStatus,
+    pub result: Vec<u8>,
+    pub state_diff: Vec<ResultAccountDiff>,
+}
+
+impl StateDiffResult {
+    pub fn new(status: TransactionStatus, result: Vec<u8>, state_diff: StateDiff) -> Self {
+        Self {
+            status,
+            result,
+            state_diff: state_diff
+                .into_iter()
+                .map(ResultAccountDiff::from)
+                .collect(),
+        }
+    }
+}
+
+pub const BLOOM_SIZE: usize = 256;
+
+/// 2048-bit logs bloom filter, as in Ethereum block headers and receipts.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Eq, PartialEq)]
+pub struct Bloom(pub [u8; BLOOM_SIZE]);
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Bloom([0u8; BLOOM_SIZE])
+    }
+}
+
+impl Bloom {
+    /// Sets the 3 bits selected by the keccak of `input`.
+    pub fn accrue(&mut self, input: &[u8]) {
+        let hash = keccak(input);
+        for i in 0..3 {
+            let bit =
+                ((hash[2 * i] as usize) << 8 | hash[2 * i + 1] as usize) & (BLOOM_SIZE * 8 - 1);
+            self.0[BLOOM_SIZE - 1 - bit / 8] |= 1 << (bit % 8);
+        }
+    }
+
+    /// Adds address and topics of the log.
+    pub fn accrue_log(&mut self, log: &Log) {
+        self.accrue(&log.address.0);
+        for topic in log.topics.iter() {
+            self.accrue(&topic.0);
+        }
+    }
+
+    pub fn accrue_bloom(&mut self, other: &Bloom) {
+        for (byte, other_byte) in self.0.iter_mut().zip(other.0.iter()) {
+            *byte |= other_byte;
+        }
+    }
+
+    /// Whether `input` may have been added to the bloom.
+    pub fn contains(&self, input: &[u8]) -> bool {
+        let mut bloom = Bloom::default();
+        bloom.accrue(input);
+        self.0
+            .iter()
+            .zip(bloom.0.iter())
+            .all(|(byte, other_byte)| byte & other_byte == *other_byte)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|byte| *byte == 0)
+    }
+}
+
+/// Error or fatal error of a transaction, with where it happened.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Eq, PartialEq)]
+pub struct ExecutionError {
+    pub reason: ExitReason,
+    /// Account whose code was running, if the error happened in its code.
+    pub address: Option<RawAddress>,
+    pub pc: Option<u64>,
+    /// Opcode at `pc`, `None` past the end of the code.
+    pub opcode: Option<u8>,
+}
+
+impl ExecutionError {
+    pub fn new(reason: ExitReason, location: Option<&ErrorLocation>) -> Self {
+        Self {
+            reason,
+            address: location.map(|location| location.address.0),
+            pc: location.map(|location| location.pc as u64),
+            opcode: location.and_then(|location| location.opcode),
+        }
+    }
+
+    /// Human readable description, e.g.
+    /// `InvalidJump in 0x0000000000000000000000000000000000000001 at pc 3 (JUMP)`.
+    pub fn message(&self) -> String {
+        let mut message = match &self.reason {
+            ExitReason::Error(error) => String::from(&error.message()[..]),
+            ExitReason::Fatal(fatal) => String::from(&fatal.message()[..]),
+            reason => format!("{:?}", reason),
+        };
+        if let Some(address) = &self.address {
+            message.push_str(" in 0x");
+            message.push_str(&bytes_to_hex(address));
+        }
+        if let Some(pc) = self.pc {
+            message.push_str(&format!(" at pc {}", pc));
+            match self.opcode.map(|opcode| (opcode, mnemonic(opcode))) {
+                Some((_, Some(name))) => message.push_str(&format!(" ({})", name)),
+                Some((opcode, None)) => message.push_str(&format!(" (0x{:02x})", opcode)),
+                None => (),
+            }
+        }
+        message
+    }
+}
+
+/// Outcome of a transaction.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Eq, PartialEq)]
+pub enum TransactionStatus {
+    Succeed,
+    /// Revert with the message of its revert data, see `revert_message`.
+    Revert(String),
+    Error(ExecutionError),
+}
+
+impl TransactionStatus {
+    pub fn new(reason: ExitReason, result: &[u8], error_location: Option<&ErrorLocation>) -> Self {
+        match reason {
+            ExitReason::Succeed(_) => TransactionStatus::Succeed,
+            ExitReason::Revert(_) => TransactionStatus::Revert(revert_message(result)),
+            reason => TransactionStatus::Error(ExecutionError::new(reason, error_location)),
+        }
+    }
+}
+
+/// Receipt returned by state changing calls.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Eq, PartialEq)]
+pub struct TransactionReceipt {
+    pub status: TransactionStatus,
+    /// NEAR gas burnt by the call, see the crate docs on gas. Filled in by the
+    /// contract, 0 otherwise.
+    pub gas_used: u64,
+    /// Return data, or revert data if the transaction reverted.
+    pub result: Vec<u8>,
+    /// Address of the created contract, if any.
+    pub contract_address: Option<RawAddress>,
+    pub logs: Vec<ResultLog>,
+    pub logs_bloom: Bloom,
+}
+
+impl TransactionReceipt {
+    pub fn new(
+        status: TransactionStatus,
+        result: Vec<u8>,
+        contract_address: Option<H160>,
+        logs: Vec<Log>,
+    ) -> Self {
+        let mut logs_bloom = Bloom::default();
+        for log in logs.iter() {
+            logs_bloom.accrue_log(log);
+        }
+        Self {
+            status,
+            gas_used: 0,
+            result,
+            contract_address: contract_address.map(|address| address.0),
+            logs: logs.into_iter().map(ResultLog::from).collect(),
+            logs_bloom,
+        }
+    }
+}
+
+/// Transfer to a NEAR account emitted by the exit to NEAR precompile.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct ExitToNearArgs {
+    pub sender: RawAddress,
+    pub account_id: Vec<u8>,
+    pub amount: RawU256,
+}
+
+/// Transfer of a NEP-141 token emitted by the exit fungible token to NEAR
+/// precompile on behalf of the connected `erc20` contract.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct ExitFtToNearArgs {
+    pub erc20: RawAddress,
+    pub nep141: Vec<u8>,
+    pub account_id: Vec<u8>,
+    pub amount: RawU256,
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct DeployErc20TokenArgs {
+    pub nep141: Vec<u8>,
+    pub code: Vec<u8>,
+}
+
+/// EIP-1186 proof of an account and some of its storage slots.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Eq, PartialEq)]
+pub struct AccountProof {
+    pub address: RawAddress,
+    pub balance: RawU256,
+    pub code_hash: RawH256,
+    pub nonce: RawU256,
+    pub storage_hash: RawH256,
+    /// Trie nodes from the state root to the account.
+    pub account_proof: Vec<Vec<u8>>,
+    pub storage_proof: Vec<StorageProof>,
+}
+
+/// EIP-1186 proof of a storage slot.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Eq, PartialEq)]
+pub struct StorageProof {
+    pub key: RawH256,
+    pub value: RawU256,
+    /// Trie nodes from the storage root to the slot.
+    pub proof: Vec<Vec<u8>>,
+}
+
+/// Account imported by `import_state`. An account with more storage than
+/// fits in one call can be split across batches: `code` is only set when
+/// not empty, and `storage` slots are added to the ones already imported.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Eq, PartialEq)]
+pub struct ImportAccount {
+    pub address: RawAddress,
+    pub nonce: RawU256,
+    pub balance: RawU256,
+    pub code: Vec<u8>,
+    pub storage: Vec<(RawH256, RawH256)>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Eq, PartialEq)]
+pub struct ImportStateArgs {
+    pub accounts: Vec<ImportAccount>,
+    /// Locks further imports after this batch.
+    pub finalize: bool,
+}
+
+impl ImportStateArgs {
+    /// Splits `accounts` into batches of about `max_bytes` of account data,
+    /// counting the address, nonce, balance, code and storage slots, and
+    /// splitting the storage of large accounts across batches. An account
+    /// with more code than `max_bytes` gets a batch of its own. Only the
+    /// last batch is marked with `finalize`.
+    pub fn batches(accounts: Vec<ImportAccount>, max_bytes: usize, finalize: bool) -> Vec<Self> {
+        const ACCOUNT_BYTES: usize = 20 + 32 + 32;
+        const SLOT_BYTES: usize = 32 + 32;
+        let mut batches = Vec::new();
+        let mut batch = Vec::new();
+        let mut size = 0;
+        for mut account in accounts {
+            loop {
+                let account_size = ACCOUNT_BYTES + account.code.len();
+                let min_size = account_size + core::cmp::min(account.storage.len(), 1) * SLOT_BYTES;
+                if !batch.is_empty() && size + min_size > max_bytes {
+                    batches.push(Self {
+                        accounts: core::mem::take(&mut batch),
+                        finalize: false,
+                    });
+                    size = 0;
+                }
+                // At least one slot is imported, to make progress.
+                let room = max_bytes.saturating_sub(size + account_size) / SLOT_BYTES;
+                let len = core::cmp::min(account.storage.len(), core::cmp::max(room, 1));
+                let rest = account.storage.split_off(len);
+                size += account_size + len * SLOT_BYTES;
+                if rest.is_empty() {
+                    batch.push(account);
+                    break;
+                }
+                // The code is already in this batch.
+                let next = ImportAccount {
+                    code: Vec::new(),
+                    storage: rest,
+                    ..account
+                };
+                batch.push(account);
+                batches.push(Self {
+                    accounts: core::mem::take(&mut batch),
+                    finalize: false,
+                });
+                size = 0;
+                account = next;
+            }
+        }
+        if !batch.is_empty() || batches.is_empty() {
+            batches.push(Self {
+                accounts: batch,
+                finalize: false,
+            });
+        }
+        if let Some(last) = batches.last_mut() {
+            last.finalize = finalize;
+        }
+        batches
+    }
+}
+
+/// Account state stored by the NEAR backend under `KeyPrefix::Account`, so
+/// that all of it is read at once.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Eq, PartialEq)]
+pub struct AccountRecord {
+    pub nonce: RawU256,
+    pub balance: RawU256,
+    /// Hash of the code, `keccak` of empty code if there is none.
+    pub code_hash: RawH256,
+    pub code_size: u64,
+    /// Storage generation, see `storage_to_key`.
+    pub generation: u32,
+    /// Bytes of NEAR storage paid for by the account and not refunded yet.
+    pub storage_paid: u64,
+}
+
+impl Default for AccountRecord {
+    fn default() -> Self {
+        Self {
+            nonce: [0u8; 32],
+            balance: [0u8; 32],
+            code_hash: keccak(&[]).0,
+            code_size: 0,
+            generation: 0,
+            storage_paid: 0,
+        }
+    }
+}
+
+impl AccountRecord {
+    pub fn is_empty(&self) -> bool {
+        self.nonce == [0u8; 32] && self.balance == [0u8; 32] && self.code_size == 0
+    }
+}
+
 pub enum KeyPrefix {
+    /// Code by its hash. Legacy layout stored code by address.
     Code = 0x0,
+    /// Legacy, migrated to `Account`.
     Balance = 0x1,
+    /// Legacy, migrated to `Account`.
     Nonce = 0x2,
+    /// Storage slots of the first generation, see `storage_to_key`.
     Storage = 0x3,
+    Nep141Erc20 = 0x4,
+    Erc20Nep141 = 0x5,
+    Bloom = 0x6,
+    /// Number of addresses using the code, by code hash.
+    CodeRefCount = 0x7,
+    /// `AccountRecord` by address.
+    Account = 0x8,
+    /// Contract configuration values by name.
+    Config = 0x9,
+    /// Storage slots of generations after the first, see `storage_to_key`.
+    StorageGeneration = 0xa,
 }
 
 pub fn address_to_key(prefix: KeyPrefix, address: &H160) -> [u8; 21] {
@@ -52,11 +469,37 @@ pub fn address_to_key(prefix: KeyPrefix, address: &H160) -> [u8; 21] {
     result
 }
 
-pub fn storage_to_key(address: &H160, key: &H256) -> [u8; 53] {
-    let mut result = [0u8; 53];
-    result[0] = KeyPrefix::Storage as u8;
-    result[1..21].copy_from_slice(&address.0);
-    result[21..].copy_from_slice(&key.0);
+pub fn hash_to_key(prefix: KeyPrefix, hash: &H256) -> [u8; 33] {
+    let mut result = [0u8; 33];
+    result[0] = prefix as u8;
+    result[1..].copy_from_slice(&hash.0);
+    result
+}
+
+pub fn bytes_to_key(prefix: KeyPrefix, bytes: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(1 + bytes.len());
+    result.push(prefix as u8);
+    result.extend_from_slice(bytes);
+    result
+}
+
+/// Storage key of slot `key` of `address`. Slots are namespaced by the
+/// storage generation of the address, so that bumping the generation makes
+/// all previous slots unreachable.
+///
+/// Generation 0 keeps the original `Storage` keys without a generation, so
+/// that slots written before storage generations existed stay readable.
+pub fn storage_to_key(address: &H160, generation: u32, key: &H256) -> Vec<u8> {
+    let mut result = Vec::with_capacity(57);
+    if generation == 0 {
+        result.push(KeyPrefix::Storage as u8);
+        result.extend_from_slice(&address.0);
+    } else {
+        result.push(KeyPrefix::StorageGeneration as u8);
+        result.extend_from_slice(&address.0);
+        result.extend_from_slice(&generation.to_be_bytes());
+    }
+    result.extend_from_slice(&key.0);
     result
 }
 
@@ -66,6 +509,12 @@ pub fn u256_to_arr(value: &U256) -> [u8; 32] {
     result
 }
 
+/// Version byte of the log encoding produced by `log_to_versioned_bytes`.
+/// Legacy encoding from `log_to_bytes` starts with the number of topics,
+/// which is at most 4, so versions start at `0x80`.
+pub const LOG_VERSION_1: u8 = 0x81;
+
+/// Legacy log encoding: number of topics, topics and data.
 pub fn log_to_bytes(log: Log) -> Vec<u8> {
     let mut result = vec![0u8; 1 + log.topics.len() * 32 + log.data.len()];
     result[0] = log.topics.len() as u8;
@@ -78,6 +527,76 @@ pub fn log_to_bytes(log: Log) -> Vec<u8> {
     result
 }
 
+/// Versioned log encoding: `LOG_VERSION_1` followed by borsh-serialized
+/// index of the log within the transaction and `ResultLog`.
+pub fn log_to_versioned_bytes(log: Log, index: u32) -> Vec<u8> {
+    let mut result = vec![LOG_VERSION_1];
+    (index, ResultLog::from(log))
+        .serialize(&mut result)
+        .unwrap();
+    result
+}
+
+/// Log as a NEAR event (NEP-297), e.g.
+/// `EVENT_JSON:{"standard":"evm","version":"1.0.0","event":"log","data":[{..}]}`.
+pub fn log_to_event_json(log: &Log, index: u32) -> String {
+    let mut result = String::from(
+        "EVENT_JSON:{\"standard\":\"evm\",\"version\":\"1.0.0\",\"event\":\"log\",\"data\":[{",
+    );
+    result.push_str("\"log_index\":");
+    result.push_str(&u128_to_dec(index.into()));
+    result.push_str(",\"address\":\"0x");
+    result.push_str(&bytes_to_hex(&log.address.0));
+    result.push_str("\",\"topics\":[");
+    for (i, topic) in log.topics.iter().enumerate() {
+        if i > 0 {
+            result.push(',');
+        }
+        result.push_str("\"0x");
+        result.push_str(&bytes_to_hex(&topic.0));
+        result.push('"');
+    }
+    result.push_str("],\"data\":\"0x");
+    result.push_str(&bytes_to_hex(&log.data));
+    result.push_str("\"}]}");
+    result
+}
+
+/// Decimal representation of `value`.
+pub fn u128_to_dec(mut value: u128) -> String {
+    let mut digits = Vec::new();
+    loop {
+        digits.push(b'0' + (value % 10) as u8);
+        value /= 10;
+        if value == 0 {
+            break;
+        }
+    }
+    digits.iter().rev().map(|digit| *digit as char).collect()
+}
+
+/// Checks that given bytes are a valid NEAR account id.
+pub fn is_valid_account_id(account_id: &[u8]) -> bool {
+    if account_id.len() < 2 || account_id.len() > 64 {
+        return false;
+    }
+    // Separators can't be leading, trailing or follow each other.
+    let mut last_is_separator = true;
+    for c in account_id {
+        match *c {
+            b'a'..=b'z' | b'0'..=b'9' => last_is_separator = false,
+            b'-' | b'_' | b'.' => {
+                if last_is_separator {
+                    return false;
+                }
+                last_is_separator = true;
+            }
+            _ => return false,
+        }
+    }
+    !last_is_separator
+}
+
 const HEX_ALPHABET: &[u8; 16] = b"0123456789abcdef";
 
 pub fn bytes_to_hex(v: &[u8]) -> String {
@@ -89,6 +608,103 @@ pub fn bytes_to_hex(v: &[u8]) -> String {
     result
 }
 
+/// Decodes hex without `0x` prefix. Odd length input is read as if it had a
+/// leading zero.
+pub fn hex_to_bytes(v: &str) -> Option<Vec<u8>> {
+    let v = v.as_bytes();
+    let mut result = Vec::with_capacity((v.len() + 1) / 2);
+    let (first, rest) = v.split_at(v.len() % 2);
+    if let Some(c) = first.first() {
+        result.push(hex_digit(*c)?);
+    }
+    for pair in rest.chunks(2) {
+        result.push((hex_digit(pair[0])? << 4) | hex_digit(pair[1])?);
+    }
+    Some(result)
+}
+
+/// Value of an ASCII hex digit.
+pub(crate) fn hex_digit(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Selector of Solidity's `Error(string)`, used by `revert("...")` and
+/// failed `require`s.
+pub const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// Message of revert data encoded as `Error(string)`.
+pub fn decode_revert_reason(data: &[u8]) -> Option<String> {
+    if data.len() < 4 || data[..4] != ERROR_SELECTOR {
+        return None;
+    }
+    let data = &data[4..];
+    let offset = abi_usize(data, 0)?;
+    let len = abi_usize(data, offset)?;
+    let start = offset.checked_add(32)?;
+    let message = data.get(start..start.checked_add(len)?)?;
+    String::from_utf8(message.to_vec()).ok()
+}
+
+/// Selector of Solidity's `Panic(uint256)`, used by failed `assert`s and
+/// checked arithmetic.
+pub const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// Code of revert data encoded as `Panic(uint256)`.
+pub fn decode_panic_code(data: &[u8]) -> Option<U256> {
+    if data.len() != 36 || data[..4] != PANIC_SELECTOR {
+        return None;
+    }
+    Some(U256::from_big_endian(&data[4..]))
+}
+
+/// Description of a Solidity panic code.
+pub fn panic_code_description(code: U256) -> &'static str {
+    if code > U256::from(0xff) {
+        return "unknown panic";
+    }
+    match code.as_u32() {
+        0x00 => "generic compiler panic",
+        0x01 => "assertion failed",
+        0x11 => "arithmetic overflow or underflow",
+        0x12 => "division or modulo by zero",
+        0x21 => "invalid enum value",
+        0x22 => "invalid encoded storage byte array",
+        0x31 => "pop on empty array",
+        0x32 => "array index out of bounds",
+        0x41 => "out of memory",
+        0x51 => "call to uninitialized internal function",
+        _ => "unknown panic",
+    }
+}
+
+/// Human readable message of revert data: the reason of an `Error(string)`,
+/// the description of a `Panic(uint256)`, or the data in 0x prefixed hex
+/// otherwise, e.g. for custom errors.
+pub fn revert_message(data: &[u8]) -> String {
+    if let Some(reason) = decode_revert_reason(data) {
+        return reason;
+    }
+    if let Some(code) = decode_panic_code(data) {
+        return format!("Panic(0x{:x}): {}", code, panic_code_description(code));
+    }
+    format!("0x{}", bytes_to_hex(data))
+}
+
+/// ABI encoded word at `offset` of `data`, if it fits in `usize`.
+fn abi_usize(data: &[u8], offset: usize) -> Option<usize> {
+    let word = data.get(offset..offset.checked_add(32)?)?;
+    let value = U256::from_big_endian(word);
+    if value > U256::from(usize::max_value()) {
+        return None;
+    }
+    Some(value.as_usize())
+}
+
 #[cfg(feature = "contract")]
 #[inline]
 pub fn keccak(data: &[u8]) -> H256 {
@@ -115,5 +731,177 @@ mod tests {
             bytes_to_hex(&[0u8, 1u8, 255u8, 16u8]),
             "0001ff10".to_string()
         );
+        assert_eq!(hex_to_bytes("0001fF10"), Some(vec![0u8, 1u8, 255u8, 16u8]));
+        assert_eq!(hex_to_bytes("abc"), Some(vec![0x0au8, 0xbc]));
+        assert_eq!(hex_to_bytes("0g"), None);
+    }
+
+    #[test]
+    fn test_revert_reason() {
+        let mut data = ERROR_SELECTOR.to_vec();
+        data.extend_from_slice(&u256_to_arr(&U256::from(32)));
+        data.extend_from_slice(&u256_to_arr(&U256::from(5)));
+        data.extend_from_slice(b"oops!");
+        data.resize(4 + 96, 0);
+        assert_eq!(decode_revert_reason(&data), Some("oops!".to_string()));
+        assert_eq!(decode_revert_reason(&data[..4 + 64]), None);
+        assert_eq!(decode_revert_reason(&[0xde, 0xad, 0xbe, 0xef]), None);
+        assert_eq!(revert_message(&data), "oops!");
+
+        let mut data = PANIC_SELECTOR.to_vec();
+        data.extend_from_slice(&u256_to_arr(&U256::from(0x11)));
+        assert_eq!(decode_panic_code(&data), Some(U256::from(0x11)));
+        assert_eq!(
+            revert_message(&data),
+            "Panic(0x11): arithmetic overflow or underflow"
+        );
+        assert_eq!(revert_message(&[0xde, 0xad, 0xbe, 0xef]), "0xdeadbeef");
+    }
+
+    #[test]
+    fn test_log_encoding() {
+        let log = Log {
+            address: H160::from_low_u64_be(1),
+            topics: vec![H256::from_low_u64_be(2)],
+            data: vec![3],
+        };
+        let bytes = log_to_versioned_bytes(log.clone(), 7);
+        assert_eq!(bytes[0], LOG_VERSION_1);
+        let (index, decoded) = <(u32, ResultLog)>::try_from_slice(&bytes[1..]).unwrap();
+        assert_eq!(index, 7);
+        assert_eq!(decoded, ResultLog::from(log.clone()));
+        assert_eq!(
+            log_to_event_json(&log, 12),
+            format!(
+                "EVENT_JSON:{{\"standard\":\"evm\",\"version\":\"1.0.0\",\"event\":\"log\",\
+                 \"data\":[{{\"log_index\":12,\"address\":\"0x{}\",\"topics\":[\"0x{}\"],\
+                 \"data\":\"0x03\"}}]}}",
+                bytes_to_hex(&log.address.0),
+                bytes_to_hex(&log.topics[0].0)
+            )
+        );
+    }
+
+    #[test]
+    fn test_import_batches() {
+        let account = |address: u8, code: &[u8], slots: u8| ImportAccount {
+            address: [address; 20],
+            nonce: [0u8; 32],
+            balance: [0u8; 32],
+            code: code.to_vec(),
+            storage: (0..slots).map(|i| ([i; 32], [1u8; 32])).collect(),
+        };
+        // An account with one byte of code and two slots.
+        let max_bytes = 84 + 1 + 2 * 64;
+        let batches = ImportStateArgs::batches(
+            vec![
+                account(1, &[1], 1),
+                account(2, &[2], 5),
+                account(3, &[], 0),
+                account(4, &[4; 300], 1),
+                account(5, &[], 1),
+            ],
+            max_bytes,
+            true,
+        );
+        assert_eq!(
+            batches
+                .iter()
+                .map(|batch| (
+                    batch
+                        .accounts
+                        .iter()
+                        .map(|account| (
+                            account.address[0],
+                            account.code.len(),
+                            account.storage.len()
+                        ))
+                        .collect::<Vec<_>>(),
+                    batch.finalize
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (vec![(1, 1, 1)], false),
+                (vec![(2, 1, 2)], false),
+                (vec![(2, 0, 2)], false),
+                (vec![(2, 0, 1)], false),
+                (vec![(3, 0, 0)], false),
+                (vec![(4, 300, 1)], false),
+                (vec![(5, 0, 1)], true),
+            ]
+        );
+        assert_eq!(ImportStateArgs::batches(Vec::new(), 2, true).len(), 1);
+    }
+
+    #[test]
+    fn test_bloom() {
+        let expected = hex::decode(
+            "00000000000000000000000000000000\
+             00000000100000000000000000000000\
+             00000000000000000000000000000000\
+             00000000000000000000000000000000\
+             00000000000000000000000000000000\
+             00000000000000000000000000000000\
+             00000002020000000000000000000000\
+             00000000000000000000000800000000\
+             10000000000000000000000000000000\
+             00000000000000000000001000000000\
+             00000000000000000000000000000000\
+             00000000000000000000000000000000\
+             00000000000000000000000000000000\
+             00000000000000000000000000000000\
+             00000000000000000000000000000000\
+             00000000000000000000000000000000",
+        )
+        .unwrap();
+        let log = Log {
+            address: H160::from_slice(
+                &hex::decode("ef2d6d194084c2de36e0dabfce45d046b37d1106").unwrap(),
+            ),
+            topics: vec![H256::from_slice(
+                &hex::decode("02c69be41d0b7e40352fc85be1cd65eb03d40ef8427a0ca4596b1ead9a00e9fc")
+                    .unwrap(),
+            )],
+            data: vec![],
+        };
+        let mut bloom = Bloom::default();
+        assert!(!bloom.contains(&log.address.0));
+        bloom.accrue_log(&log);
+        assert_eq!(bloom.0.to_vec(), expected);
+        assert!(bloom.contains(&log.address.0));
+        assert!(bloom.contains(&log.topics[0].0));
+    }
+
+    #[test]
+    fn test_account_id() {
+        assert!(is_valid_account_id(b"alice.near"));
+        assert!(is_valid_account_id(b"a-b_c.testnet"));
+        assert!(!is_valid_account_id(b"a"));
+        assert!(!is_valid_account_id(b"Alice.near"));
+        assert!(!is_valid_account_id(b".alice"));
+        assert!(!is_valid_account_id(b"alice..near"));
+        assert!(!is_valid_account_id(b"alice."));
+    }
+
+    #[test]
+    fn test_execution_error() {
+        let error = ExecutionError {
+            reason: ExitFatal::CallErrorAsFatal(ExitError::Other("oops".into())).into(),
+            address: Some([1; 20]),
+            pc: Some(7),
+            opcode: Some(0xf1),
+        };
+        assert_eq!(
+            error.message(),
+            format!(
+                "CallErrorAsFatal(oops) in 0x{} at pc 7 (CALL)",
+                "01".repeat(20)
+            )
+        );
+        let status = TransactionStatus::Error(error);
+        assert_eq!(
+            TransactionStatus::try_from_slice(&status.try_to_vec().unwrap()).unwrap(),
+            status
+        );
     }
 }
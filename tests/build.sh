@@ -8,6 +8,7 @@ contracts=(
     "ConstructorRevert"
     "PrecompiledFunction"
     "TToken"
+    "EvmErc20"
     "BNum"
     "BToken"
     "BMath"
@@ -0,0 +1,126 @@
+pragma solidity >=0.5.12;
+
+// ERC-20 proxy of a NEP-141 token, deployed and minted by the NEAR EVM
+// connector (see `deploy_erc20_token` and `ft_on_transfer`).
+
+contract EvmErc20 {
+
+    // `keccak("exitFtToNear")[12..]`
+    address constant EXIT_FT_TO_NEAR = 0xB4eeC51C2B5879E479095DC219AD4edF9f1D30B5;
+
+    string private _name;
+    string private _symbol;
+    uint8   private _decimals;
+
+    address private _admin;
+
+    uint internal _totalSupply;
+
+    mapping(address => uint)                   private _balance;
+    mapping(address => mapping(address=>uint)) private _allowance;
+
+    modifier _onlyAdmin_() {
+        require(msg.sender == _admin, "ERR_NOT_ADMIN");
+        _;
+    }
+
+    event Approval(address indexed src, address indexed dst, uint amt);
+    event Transfer(address indexed src, address indexed dst, uint amt);
+
+    // Math
+    function add(uint a, uint b) internal pure returns (uint c) {
+        require((c = a + b) >= a);
+    }
+    function sub(uint a, uint b) internal pure returns (uint c) {
+        require((c = a - b) <= a);
+    }
+
+    constructor(
+        string memory name,
+        string memory symbol,
+        uint8 decimals
+    ) public {
+        _name = name;
+        _symbol = symbol;
+        _decimals = decimals;
+        _admin = msg.sender;
+    }
+
+    function name() public view returns (string memory) {
+        return _name;
+    }
+
+    function symbol() public view returns (string memory) {
+        return _symbol;
+    }
+
+    function decimals() public view returns(uint8) {
+        return _decimals;
+    }
+
+    function _move(address src, address dst, uint amt) internal {
+        require(_balance[src] >= amt, "ERR_INSUFFICIENT_BAL");
+        _balance[src] = sub(_balance[src], amt);
+        _balance[dst] = add(_balance[dst], amt);
+        emit Transfer(src, dst, amt);
+    }
+
+    function _mint(address dst, uint amt) internal {
+        _balance[dst] = add(_balance[dst], amt);
+        _totalSupply = add(_totalSupply, amt);
+        emit Transfer(address(0), dst, amt);
+    }
+
+    function _burn(address src, uint amt) internal {
+        require(_balance[src] >= amt, "ERR_INSUFFICIENT_BAL");
+        _balance[src] = sub(_balance[src], amt);
+        _totalSupply = sub(_totalSupply, amt);
+        emit Transfer(src, address(0), amt);
+    }
+
+    function allowance(address src, address dst) external view returns (uint) {
+        return _allowance[src][dst];
+    }
+
+    function balanceOf(address whom) external view returns (uint) {
+        return _balance[whom];
+    }
+
+    function totalSupply() public view returns (uint) {
+        return _totalSupply;
+    }
+
+    function approve(address dst, uint amt) external returns (bool) {
+        _allowance[msg.sender][dst] = amt;
+        emit Approval(msg.sender, dst, amt);
+        return true;
+    }
+
+    // Called by the connector when tokens are transferred to the EVM.
+    function mint(address dst, uint256 amt) public _onlyAdmin_ returns (bool) {
+        _mint(dst, amt);
+        return true;
+    }
+
+    // Burns tokens and sends them to `recipient` NEAR account.
+    function withdrawToNear(bytes memory recipient, uint amt) public {
+        _burn(msg.sender, amt);
+        (bool success, ) = EXIT_FT_TO_NEAR.call(abi.encodePacked(amt, recipient));
+        require(success, "ERR_EXIT_FAILED");
+    }
+
+    function transfer(address dst, uint amt) external returns (bool) {
+        _move(msg.sender, dst, amt);
+        return true;
+    }
+
+    function transferFrom(address src, address dst, uint amt) external returns (bool) {
+        require(msg.sender == src || amt <= _allowance[src][msg.sender], "ERR_BTOKEN_BAD_CALLER");
+        _move(src, dst, amt);
+        if (msg.sender != src && _allowance[src][msg.sender] != uint256(-1)) {
+            _allowance[src][msg.sender] = sub(_allowance[src][msg.sender], amt);
+            emit Approval(msg.sender, dst, _allowance[src][msg.sender]);
+        }
+        return true;
+    }
+}
@@ -0,0 +1,434 @@
+//! Runs the GeneralStateTests and VMTests fixtures of ethereum/tests from a
+//! local checkout, given by the `ETHEREUM_TESTS` environment variable:
+//!
+//! ```shell
+//! ETHEREUM_TESTS=../tests cargo test --features serde --test ethereum_tests -- --nocapture
+//! ```
+//!
+//! Prints pass, fail and skip counts per fork, and each failure and skip with
+//! `ETHEREUM_TESTS_VERBOSE` set. The test fails if more fixtures fail than
+//! `ETHEREUM_TESTS_MAX_FAILURES` allows, 0 by default.
+//!
+//! Gas is not metered (see the crate docs), so the fees paid by senders to
+//! the coinbase are not reproduced. State tests with a non-zero gas price are
+//! checked against the accounts of their post-state instead of its root,
+//! leaving out the sender and coinbase balances, and are skipped if the
+//! fixture has no post-state. Forks whose rules are not supported are skipped
+//! too. Fixtures that expect to run out of gas fail.
+#![cfg(feature = "serde")]
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use primitive_types::{H160, H256, U256};
+use rlp::RlpStream;
+use serde_json::Value;
+
+use near_evm::backend::{
+    state_from_genesis_alloc, Log, MemoryAccount, MemoryBackend, MemoryVicinity,
+};
+use near_evm::runner::{Config, Runner};
+use near_evm::types::{hex_to_bytes, keccak};
+
+/// Pass, fail and skip counts of a fork.
+#[derive(Default)]
+struct Counts {
+    passed: usize,
+    failed: usize,
+    skipped: usize,
+}
+
+#[derive(Default)]
+struct Report {
+    forks: BTreeMap<String, Counts>,
+    verbose: bool,
+}
+
+/// Why a state test can't be run.
+enum Skip {
+    Fork,
+    GasPrice,
+}
+
+impl Report {
+    fn record(&mut self, fork: &str, name: &str, result: Result<(), String>) {
+        let counts = self.forks.entry(fork.to_string()).or_default();
+        match result {
+            Ok(()) => counts.passed += 1,
+            Err(error) => {
+                counts.failed += 1;
+                if self.verbose {
+                    println!("FAIL {} {}: {}", fork, name, error);
+                }
+            }
+        }
+    }
+
+    fn skip(&mut self, fork: &str, name: &str, reason: Skip) {
+        self.forks.entry(fork.to_string()).or_default().skipped += 1;
+        if self.verbose {
+            let reason = match reason {
+                Skip::Fork => "fork not supported",
+                Skip::GasPrice => "non-zero gas price and no post-state",
+            };
+            println!("SKIP {} {}: {}", fork, name, reason);
+        }
+    }
+
+    fn failed(&self) -> usize {
+        self.forks.values().map(|counts| counts.failed).sum()
+    }
+}
+
+/// Rules of a fork named as in the fixtures, for the forks supported.
+fn fork_config(fork: &str) -> Option<Config> {
+    match fork {
+        "Frontier" => Some(Config::frontier()),
+        "Istanbul" => Some(Config::istanbul()),
+        _ => None,
+    }
+}
+
+fn to_bytes(value: &Value) -> Vec<u8> {
+    let value = value.as_str().unwrap_or("");
+    hex_to_bytes(value.trim_start_matches("0x")).expect("invalid hex")
+}
+
+fn to_u256(value: &Value) -> U256 {
+    let value = value.as_str().expect("invalid quantity");
+    U256::from_str_radix(value.trim_start_matches("0x"), 16).expect("invalid quantity")
+}
+
+fn to_h160(value: &Value) -> H160 {
+    H160::from_slice(&to_bytes(value))
+}
+
+fn to_h256(value: &Value) -> H256 {
+    H256::from_slice(&to_bytes(value))
+}
+
+fn vicinity(env: &Value, origin: H160, gas_price: U256) -> MemoryVicinity {
+    MemoryVicinity {
+        gas_price,
+        origin,
+        chain_id: U256::one(),
+        block_hashes: Vec::new(),
+        block_number: to_u256(&env["currentNumber"]),
+        block_coinbase: to_h160(&env["currentCoinbase"]),
+        block_timestamp: to_u256(&env["currentTimestamp"]),
+        block_difficulty: to_u256(&env["currentDifficulty"]),
+        block_gas_limit: to_u256(&env["currentGasLimit"]),
+    }
+}
+
+/// Hash of the RLP list of `logs`, as in the `logs` field of fixtures.
+fn logs_hash(logs: &[Log]) -> H256 {
+    let mut stream = RlpStream::new_list(logs.len());
+    for log in logs {
+        stream.begin_list(3);
+        stream.append(&log.address);
+        stream.begin_list(log.topics.len());
+        for topic in log.topics.iter() {
+            stream.append(topic);
+        }
+        stream.append(&log.data);
+    }
+    keccak(&stream.out())
+}
+
+fn intrinsic_gas(config: &Config, data: &[u8], is_create: bool) -> U256 {
+    let base = if is_create {
+        config.gas_transaction_create
+    } else {
+        config.gas_transaction_call
+    };
+    let data_gas: usize = data
+        .iter()
+        .map(|byte| {
+            if *byte == 0 {
+                config.gas_transaction_zero_data
+            } else {
+                config.gas_transaction_non_zero_data
+            }
+        })
+        .sum();
+    U256::from(base + data_gas)
+}
+
+/// Why a transaction is invalid, if it is.
+fn validate_transaction(
+    vicinity: &MemoryVicinity,
+    account: &MemoryAccount,
+    nonce: U256,
+    gas_limit: U256,
+    intrinsic_gas: U256,
+    value: U256,
+) -> Result<(), &'static str> {
+    if account.nonce != nonce {
+        return Err("invalid nonce");
+    }
+    if !account.code.is_empty() {
+        return Err("sender is a contract");
+    }
+    if gas_limit > vicinity.block_gas_limit {
+        return Err("gas limit above block gas limit");
+    }
+    if gas_limit < intrinsic_gas {
+        return Err("gas limit below intrinsic gas");
+    }
+    let cost = gas_limit
+        .checked_mul(vicinity.gas_price)
+        .and_then(|fee| fee.checked_add(value));
+    if cost.map_or(true, |cost| cost > account.balance) {
+        return Err("insufficient balance");
+    }
+    Ok(())
+}
+
+/// Runs the transaction of `test` selected by `post` on the rules of `fork`.
+fn run_state_test(test: &Value, config: &Config, post: &Value) -> Result<(), String> {
+    let transaction = &test["transaction"];
+    let sender = match transaction.get("sender") {
+        Some(sender) => to_h160(sender),
+        None => return Err(String::from("no sender in transaction")),
+    };
+    let indexes = &post["indexes"];
+    let index = |name: &str| indexes[name].as_u64().unwrap_or(0) as usize;
+    let data = to_bytes(&transaction["data"][index("data")]);
+    let gas_limit = to_u256(&transaction["gasLimit"][index("gas")]);
+    let value = to_u256(&transaction["value"][index("value")]);
+    let gas_price = transaction.get("gasPrice").map_or_else(U256::zero, to_u256);
+    let to = to_bytes(&transaction["to"]);
+
+    let state = state_from_genesis_alloc(&test["pre"]).map_err(String::from)?;
+    let vicinity = vicinity(&test["env"], sender, gas_price);
+    let mut backend = MemoryBackend::new(&vicinity, state);
+
+    let account = backend.state().get(&sender).cloned().unwrap_or_default();
+    let mut exception = validate_transaction(
+        &vicinity,
+        &account,
+        to_u256(&transaction["nonce"]),
+        gas_limit,
+        intrinsic_gas(config, &data, to.is_empty()),
+        value,
+    )
+    .err();
+    let mut logs = Vec::new();
+    if exception.is_none() {
+        let execution =
+            Runner::execute_with_config(&mut backend, config, true, false, None, |executor| {
+                if to.is_empty() {
+                    executor.transact_create(sender, value, data)
+                } else {
+                    executor.transact_call(sender, H160::from_slice(&to), value, data)
+                }
+            });
+        if execution.reason.is_fatal() {
+            exception = Some("fatal error");
+        }
+        logs = execution.logs;
+    }
+
+    match (post.get("expectException"), exception) {
+        (Some(expected), None) => {
+            return Err(format!(
+                "expected exception {}, transaction applied",
+                expected.as_str().unwrap_or("")
+            ));
+        }
+        (None, Some(exception)) => return Err(format!("unexpected exception: {}", exception)),
+        _ => {}
+    }
+    if gas_price.is_zero() {
+        let root = backend.state_root();
+        if root != to_h256(&post["hash"]) {
+            return Err(format!("state root {:?}", root));
+        }
+    } else {
+        let fee_accounts = [sender, vicinity.block_coinbase];
+        compare_post_state(backend.state(), &post["state"], &fee_accounts)?;
+    }
+    let hash = logs_hash(&logs);
+    if hash != to_h256(&post["logs"]) {
+        return Err(format!("logs hash {:?}", hash));
+    }
+    Ok(())
+}
+
+/// Compares `state` with the accounts of `post`, an `alloc` like the
+/// pre-state. Balances of `fee_accounts` are left out, as they pay or receive
+/// the fees.
+fn compare_post_state(
+    state: &BTreeMap<H160, MemoryAccount>,
+    post: &Value,
+    fee_accounts: &[H160],
+) -> Result<(), String> {
+    let post = state_from_genesis_alloc(post).map_err(String::from)?;
+    let addresses: BTreeSet<&H160> = state.keys().chain(post.keys()).collect();
+    for address in addresses {
+        let mut account = state.get(address).cloned().unwrap_or_default();
+        let expected = post.get(address).cloned().unwrap_or_default();
+        if fee_accounts.contains(address) {
+            account.balance = expected.balance;
+        }
+        if account != expected {
+            return Err(format!("post-state mismatch of {:?}", address));
+        }
+    }
+    Ok(())
+}
+
+/// Runs a VMTests fixture. Its code runs as a call from `exec.caller`, so the
+/// value is transferred and the caller nonce increased, and only storage and
+/// code of the post-state are checked.
+fn run_vm_test(test: &Value) -> Result<(), String> {
+    let exec = &test["exec"];
+    let address = to_h160(&exec["address"]);
+    let caller = to_h160(&exec["caller"]);
+    let mut state = state_from_genesis_alloc(&test["pre"]).map_err(String::from)?;
+    state.entry(address).or_default().code = to_bytes(&exec["code"]);
+    let vicinity = vicinity(
+        &test["env"],
+        to_h160(&exec["origin"]),
+        to_u256(&exec["gasPrice"]),
+    );
+    let mut backend = MemoryBackend::new(&vicinity, state);
+    let value = to_u256(&exec["value"]);
+    let execution = Runner::execute_with_config(
+        &mut backend,
+        &Config::frontier(),
+        true,
+        false,
+        None,
+        |executor| executor.transact_call(caller, address, value, to_bytes(&exec["data"])),
+    );
+
+    let post = match test.get("post") {
+        Some(post) => state_from_genesis_alloc(post).map_err(String::from)?,
+        None if execution.reason.is_succeed() => {
+            return Err(format!("expected failure, got {:?}", execution.reason));
+        }
+        None => return Ok(()),
+    };
+    if !execution.reason.is_succeed() {
+        return Err(format!("{:?}", execution.reason));
+    }
+    if execution.result != to_bytes(&test["out"]) {
+        return Err(String::from("output mismatch"));
+    }
+    for (address, expected) in post.iter() {
+        let account = backend.state().get(address).cloned().unwrap_or_default();
+        if account.storage != expected.storage || account.code != expected.code {
+            return Err(format!("post-state mismatch of {:?}", address));
+        }
+    }
+    let hash = logs_hash(&execution.logs);
+    if hash != to_h256(&test["logs"]) {
+        return Err(format!("logs hash {:?}", hash));
+    }
+    Ok(())
+}
+
+fn run_file(path: &Path, report: &mut Report) {
+    let name = path.display().to_string();
+    let json: Value = match fs::read_to_string(path)
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+    {
+        Some(json) => json,
+        None => return report.record("invalid", &name, Err(String::from("unreadable fixture"))),
+    };
+    for (test_name, test) in json.as_object().into_iter().flatten() {
+        let name = format!("{}::{}", name, test_name);
+        if test.get("exec").is_some() {
+            report.record("VMTests", &name, run_vm_test(test));
+            continue;
+        }
+        let has_gas_price = test["transaction"]
+            .get("gasPrice")
+            .map_or(true, |gas_price| !to_u256(gas_price).is_zero());
+        for (fork, posts) in test["post"].as_object().into_iter().flatten() {
+            for post in posts.as_array().into_iter().flatten() {
+                let indexes = &post["indexes"];
+                let name = format!(
+                    "{}[{},{},{}]",
+                    name, indexes["data"], indexes["gas"], indexes["value"]
+                );
+                match fork_config(fork) {
+                    None => report.skip(fork, &name, Skip::Fork),
+                    Some(_) if has_gas_price && post.get("state").is_none() => {
+                        report.skip(fork, &name, Skip::GasPrice)
+                    }
+                    Some(config) => report.record(fork, &name, run_state_test(test, &config, post)),
+                }
+            }
+        }
+    }
+}
+
+fn json_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let mut entries: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|e| e.path())
+            .collect(),
+        Err(_) => return,
+    };
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            json_files(&path, files);
+        } else if path
+            .extension()
+            .map_or(false, |extension| extension == "json")
+        {
+            files.push(path);
+        }
+    }
+}
+
+#[test]
+fn test_ethereum_tests() {
+    let root = match env::var("ETHEREUM_TESTS") {
+        Ok(root) => PathBuf::from(root),
+        Err(_) => {
+            println!("ETHEREUM_TESTS is not set, skipping ethereum/tests fixtures");
+            return;
+        }
+    };
+    let mut files = Vec::new();
+    for dir in &[
+        "GeneralStateTests",
+        "VMTests",
+        "LegacyTests/Constantinople/VMTests",
+    ] {
+        json_files(&root.join(dir), &mut files);
+    }
+    assert!(!files.is_empty(), "no fixtures found in {}", root.display());
+
+    let mut report = Report {
+        verbose: env::var("ETHEREUM_TESTS_VERBOSE").is_ok(),
+        ..Default::default()
+    };
+    for path in files.iter() {
+        run_file(path, &mut report);
+    }
+    for (fork, counts) in report.forks.iter() {
+        println!(
+            "{}: {} passed, {} failed, {} skipped",
+            fork, counts.passed, counts.failed, counts.skipped
+        );
+    }
+    let max_failures = env::var("ETHEREUM_TESTS_MAX_FAILURES")
+        .map(|max| max.parse().expect("invalid ETHEREUM_TESTS_MAX_FAILURES"))
+        .unwrap_or(0);
+    assert!(
+        report.failed() <= max_failures,
+        "{} fixtures failed, ETHEREUM_TESTS_MAX_FAILURES is {}",
+        report.failed(),
+        max_failures
+    );
+}
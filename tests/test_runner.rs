@@ -1,10 +1,13 @@
-use borsh::BorshSerialize;
+use borsh_crate::BorshSerialize;
 use ethabi_contract::use_contract;
-use primitive_types::{H160, U256};
+use primitive_types::{H160, H256, U256};
 
 use near_evm::backend::Backend;
 use near_evm::runner::Runner;
-use near_evm::types::{near_account_to_evm_address, FunctionCallArgs, ViewCallArgs};
+use near_evm::types::{
+    keccak, near_account_to_evm_address, ExitError, FunctionCallArgs, TransactionStatus,
+    ViewCallArgs,
+};
 
 mod test_backend;
 
@@ -35,11 +38,13 @@ impl TestRunner {
     }
 
     pub fn deploy_code(&mut self, code: Vec<u8>) -> H160 {
-        Runner::deploy_code(&mut self.backend, &code).1
+        let receipt = Runner::deploy_code(&mut self.backend, &code);
+        assert_eq!(receipt.status, TransactionStatus::Succeed);
+        H160(receipt.contract_address.unwrap())
     }
 
     pub fn call(&mut self, address: H160, input: Vec<u8>) -> Vec<u8> {
-        let result = Runner::call(
+        let receipt = Runner::call(
             &mut self.backend,
             &FunctionCallArgs {
                 contract: address.0,
@@ -48,8 +53,13 @@ impl TestRunner {
             .try_to_vec()
             .unwrap(),
         );
-        assert!(result.0.is_succeed(), format!("{:?}", result.1));
-        result.1
+        assert_eq!(
+            receipt.status,
+            TransactionStatus::Succeed,
+            "{:?}",
+            receipt.result
+        );
+        receipt.result
     }
 
     pub fn view(&mut self, sender: H160, address: H160, value: U256, input: Vec<u8>) -> Vec<u8> {
@@ -123,6 +133,58 @@ fn test_ttoken() {
     );
 }
 
+#[test]
+fn test_state_diff() {
+    let mut runner = TestRunner::new();
+    let address = create_ttoken(&mut runner);
+    let (input, _) = ttoken::functions::transfer::call(&address.0, 1 * 10u128.pow(18));
+    let (receipt, state_diff) = Runner::dry_run_call(
+        &mut runner.backend,
+        &FunctionCallArgs {
+            contract: address.0,
+            input,
+        }
+        .try_to_vec()
+        .unwrap(),
+    );
+    assert_eq!(receipt.status, TransactionStatus::Succeed);
+    assert_eq!(state_diff.len(), 2);
+    let diff = &state_diff[&alice_addr()];
+    assert_eq!(diff.nonce.to, diff.nonce.from + 1);
+    assert_eq!(diff.balance.from, diff.balance.to);
+    assert!(diff.storage.is_empty());
+    // Token balances of alice and of the token contract, in `_balance` at
+    // slot 4.
+    let diff = &state_diff[&address];
+    assert_eq!(diff.nonce.from, diff.nonce.to);
+    assert_eq!(diff.balance.from, diff.balance.to);
+    let balance_slot = |owner: H160| {
+        let mut preimage = [0u8; 64];
+        preimage[12..32].copy_from_slice(&owner.0);
+        preimage[63] = 4;
+        keccak(&preimage)
+    };
+    let token = |amount: u128| {
+        let mut value = [0u8; 32];
+        value[16..].copy_from_slice(&amount.to_be_bytes());
+        H256(value)
+    };
+    let alice = &diff.storage[&balance_slot(alice_addr())];
+    assert_eq!(alice.from, token(10 * 10u128.pow(18)));
+    assert_eq!(alice.to, token(9 * 10u128.pow(18)));
+    let contract = &diff.storage[&balance_slot(address)];
+    assert_eq!(contract.from, H256::zero());
+    assert_eq!(contract.to, token(10u128.pow(18)));
+    assert_eq!(diff.storage.len(), 2);
+    // Nothing is committed.
+    let (input, _) = ttoken::functions::balance_of::call(&alice_addr().0);
+    let result = runner.view(address, address, U256::zero(), input);
+    assert_eq!(
+        U256::from_big_endian(&result),
+        U256::from(10 * 10u128.pow(18))
+    );
+}
+
 #[test]
 fn test_balancer() {
     let mut runner = TestRunner::new();
@@ -160,3 +222,24 @@ fn test_balancer() {
     .unwrap();
     assert_eq!(result, U256::zero());
 }
+
+#[test]
+fn test_error_location() {
+    let mut runner = TestRunner::new();
+    // PUSH1 0x05 JUMP
+    let receipt = Runner::deploy_code(&mut runner.backend, &[0x60, 0x05, 0x56]);
+    let error = match receipt.status {
+        TransactionStatus::Error(error) => error,
+        status => panic!("{:?}", status),
+    };
+    assert_eq!(error.reason, ExitError::InvalidJump.into());
+    assert_eq!(error.pc, Some(2));
+    assert_eq!(error.opcode, Some(0x56));
+    assert_eq!(
+        error.message(),
+        format!(
+            "InvalidJump in 0x{} at pc 2 (JUMP)",
+            hex::encode(error.address.unwrap())
+        )
+    );
+}